@@ -0,0 +1,337 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+use crate::utils::db::{self, Database};
+use crate::utils::error::{Error, Result};
+use crate::utils::{clipboard_write, settings};
+
+const IDENTIFIER: &str = "com.jackple.clippy2";
+
+const USAGE: &str = "用法: clippy2 <command> [args]
+
+  list [--limit N] [--type text|image|file] [--fav]   列出记录
+       [--subtype url|email|code|...]                按文本子类型筛选
+  search <keyword> [--limit N]                       搜索记录
+  get <id>                                           输出记录内容(图片输出原始数据)
+  copy [id] [--file]                                 写入剪贴板, 省略 id 时从 stdin 读取, --file 将图片作为文件写入
+  add                                                从 stdin 读取文本并添加
+  fav <id>                                           切换收藏状态
+  delete <id> [--force]                              删除记录, 收藏的记录需加 --force
+  export                                             以 JSON 输出全部记录
+
+示例: clippy2 search foo | fzf | clippy2 copy";
+
+const COMMANDS: [&str; 9] = [
+    "list", "search", "get", "copy", "add", "fav", "delete", "export", "help",
+];
+
+// 带子命令启动时作为命令行工具运行, 返回退出码; 否则返回 None, 正常启动界面
+pub fn run(args: Vec<String>) -> Option<i32> {
+    let command = args.first()?;
+    if !COMMANDS.contains(&command.as_str()) {
+        return None;
+    }
+
+    if command == "help" {
+        println!("{}", USAGE);
+        return Some(0);
+    }
+
+    // 与界面共用同一个数据目录, 直接读写 app.db, 完整性检查和恢复只在界面启动时进行
+    let app_dir = app_data_dir();
+    let result = settings::init_at(&app_dir)
+        .and_then(|_| db::open_at(&app_dir))
+        .and_then(|db| execute(&db, command, &args[1..], &mut std::io::stdin()));
+    match result {
+        Ok(_) => Some(0),
+        Err(e) => {
+            eprintln!("clippy2: {}", e);
+            Some(1)
+        }
+    }
+}
+
+fn app_data_dir() -> PathBuf {
+    if let Ok(dir) = std::env::var("CLIPPY2_DATA_DIR") {
+        return PathBuf::from(dir);
+    }
+    let home = std::env::var("HOME").unwrap_or_default();
+    PathBuf::from(home)
+        .join("Library/Application Support")
+        .join(IDENTIFIER)
+}
+
+// stdin 作为参数传入, 测试时可以替换
+fn execute(db: &Database, command: &str, args: &[String], stdin: &mut dyn Read) -> Result<()> {
+    match command {
+        "list" => {
+            let records = db.get_records(db::QueryParams {
+                limit: option_value(args, "--limit")
+                    .map(|v| parse_number(&v))
+                    .transpose()?
+                    .unwrap_or(db::DEFAULT_PAGE_SIZE),
+                keyword: None,
+                record_type: option_value(args, "--type"),
+                favorite: Some(args.iter().any(|a| a == "--fav")),
+                subtype: option_value(args, "--subtype"),
+                ..Default::default()
+            })?;
+            print_records(&records.records);
+        }
+        "search" => {
+            let keyword = positional(args).ok_or_else(|| missing("keyword"))?;
+            let records = db.get_records(db::QueryParams {
+                limit: option_value(args, "--limit")
+                    .map(|v| parse_number(&v))
+                    .transpose()?
+                    .unwrap_or(db::DEFAULT_PAGE_SIZE),
+                keyword: Some(keyword),
+                ..Default::default()
+            })?;
+            print_records(&records.records);
+        }
+        "get" => {
+            let id = parse_id(positional(args))?;
+            let record = db.get_record(id)?;
+            let mut stdout = std::io::stdout();
+            if record.record_type == "image" {
+                let bytes = STANDARD.decode(&record.value)?;
+                stdout.write_all(&bytes)?;
+            } else {
+                stdout.write_all(record.value.as_bytes())?;
+            }
+        }
+        "copy" => {
+            // 支持从管道读取 list/search 输出的一行, 取第一列的 id
+            let id = match positional(args) {
+                Some(id) => parse_id(Some(id))?,
+                None => parse_id(
+                    read_input(stdin)?
+                        .split_whitespace()
+                        .next()
+                        .map(String::from),
+                )?,
+            };
+            let record = db.get_record(id)?;
+            // 只写入剪贴板: 命令行进程无法让界面跳过这次写入, 界面会把它记为一次复制, 不再计为粘贴
+            clipboard_write::write_value(
+                db,
+                clipboard_write::RecordInput {
+                    id: record.id,
+                    record_type: record.record_type,
+                    value: record.value,
+                    as_file: args.iter().any(|a| a == "--file"),
+                },
+            )?;
+        }
+        "add" => {
+            let input = read_input(stdin)?;
+            // echo 等命令会在末尾加一个换行, 只去掉这一个, 内容本身的空行保留
+            let text = input.strip_suffix('\n').unwrap_or(&input);
+            if text.is_empty() {
+                return Err(Error::InvalidInput("stdin is empty".to_string()));
+            }
+            let id = db.add_record(db::RecordInput {
+                record_type: "text".to_string(),
+                value: text.to_string(),
+                thumbnail_key: None,
+                preview_key: None,
+                size: None,
+                img_size: None,
+                phash: None,
+                source_app: None,
+            })?;
+            println!("{}", id);
+        }
+        "fav" => {
+            let id = parse_id(positional(args))?;
+            db.toggle_favorite(id)?;
+            let record = db.get_record(id)?;
+            println!("{}\t{}", id, if record.favorite { "★" } else { "☆" });
+        }
+        "delete" => {
+            let id = parse_id(positional(args))?;
+            let force = args.iter().any(|a| a == "--force");
+            if db.soft_delete(&[id], force)?.is_empty() {
+                return Err(Error::InvalidInput(format!(
+                    "record {} not deleted (favorite? use --force)",
+                    id
+                )));
+            }
+        }
+        "export" => {
+            let records = db.export_records()?;
+            let json = serde_json::to_string_pretty(&records)?;
+            println!("{}", json);
+        }
+        _ => return Err(Error::InvalidInput(format!("unknown command: {}", command))),
+    }
+    Ok(())
+}
+
+// 每行: id<TAB>类型<TAB>单行预览
+fn print_records(records: &[db::Record]) {
+    let mut stdout = std::io::stdout().lock();
+    for record in records {
+        let preview = if record.record_type == "image" {
+            "[image]".to_string()
+        } else {
+            record
+                .value
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+                .chars()
+                .take(200)
+                .collect()
+        };
+        let star = if record.favorite { "★ " } else { "" };
+        // 下游(如 head)提前关闭管道时直接结束
+        if writeln!(
+            stdout,
+            "{}\t{}\t{}{}",
+            record.id, record.record_type, star, preview
+        )
+        .is_err()
+        {
+            break;
+        }
+    }
+}
+
+fn read_input(stdin: &mut dyn Read) -> Result<String> {
+    let mut input = String::new();
+    stdin.read_to_string(&mut input)?;
+    Ok(input)
+}
+
+// 取 `--name value` 形式的参数
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1).cloned())
+}
+
+// 取第一个不属于选项的参数
+fn positional(args: &[String]) -> Option<String> {
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        if arg == "--fav" || arg == "--force" || arg == "--file" {
+            continue;
+        }
+        if arg.starts_with("--") {
+            iter.next();
+            continue;
+        }
+        return Some(arg.clone());
+    }
+    None
+}
+
+fn parse_number(value: &str) -> Result<u32> {
+    value
+        .parse()
+        .map_err(|_| Error::InvalidInput(format!("invalid number: {}", value)))
+}
+
+fn missing(name: &str) -> Error {
+    Error::InvalidInput(format!("missing <{}>", name))
+}
+
+fn parse_id(value: Option<String>) -> Result<i64> {
+    let value = value.ok_or_else(|| missing("id"))?;
+    value
+        .trim()
+        .parse()
+        .map_err(|_| Error::InvalidInput(format!("invalid id: {}", value)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|a| a.to_string()).collect()
+    }
+
+    fn open() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        (dir, db)
+    }
+
+    fn add(db: &Database, stdin: &str) -> Result<()> {
+        execute(db, "add", &[], &mut stdin.as_bytes())
+    }
+
+    fn invalid_input(result: Result<impl std::fmt::Debug>) -> String {
+        match result {
+            Err(Error::InvalidInput(message)) => message,
+            other => panic!("expected invalid input, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn options_and_positional_args_are_parsed() {
+        let list = args(&["--limit", "5", "--fav", "foo", "--type", "text"]);
+        assert_eq!(option_value(&list, "--limit").as_deref(), Some("5"));
+        assert_eq!(option_value(&list, "--type").as_deref(), Some("text"));
+        assert_eq!(option_value(&list, "--subtype"), None);
+        assert_eq!(positional(&list).as_deref(), Some("foo"));
+
+        // 选项的值不会被当作位置参数
+        assert_eq!(positional(&args(&["--limit", "5"])), None);
+        assert_eq!(positional(&args(&["--force", "12"])).as_deref(), Some("12"));
+        assert_eq!(option_value(&args(&["--limit"]), "--limit"), None);
+
+        assert_eq!(parse_id(Some(" 12\n".to_string())).unwrap(), 12);
+        assert_eq!(parse_number("20").unwrap(), 20);
+    }
+
+    #[test]
+    fn missing_and_invalid_args_are_invalid_input() {
+        let (_dir, db) = open();
+        let mut stdin = "".as_bytes();
+        let mut run = |command: &str, list: &[&str]| execute(&db, command, &args(list), &mut stdin);
+
+        assert_eq!(invalid_input(run("get", &[])), "missing <id>");
+        assert_eq!(invalid_input(run("fav", &["--force"])), "missing <id>");
+        assert_eq!(
+            invalid_input(run("search", &["--limit", "5"])),
+            "missing <keyword>"
+        );
+        assert_eq!(invalid_input(run("delete", &["abc"])), "invalid id: abc");
+        assert_eq!(
+            invalid_input(run("list", &["--limit", "x"])),
+            "invalid number: x"
+        );
+        // stdin 为空时 copy 同样缺少 id
+        assert_eq!(invalid_input(run("copy", &[])), "missing <id>");
+    }
+
+    #[test]
+    fn add_trims_one_trailing_newline() {
+        let (_dir, db) = open();
+        add(&db, "hello\n").unwrap();
+        add(&db, "two lines\n\n").unwrap();
+        add(&db, "no newline").unwrap();
+        add(&db, "  spaced  \n").unwrap();
+
+        let mut values: Vec<_> = db
+            .export_records()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.value)
+            .collect();
+        values.sort();
+        assert_eq!(
+            values,
+            vec!["  spaced  ", "hello", "no newline", "two lines\n"]
+        );
+
+        assert_eq!(invalid_input(add(&db, "")), "stdin is empty");
+        assert_eq!(invalid_input(add(&db, "\n")), "stdin is empty");
+    }
+}
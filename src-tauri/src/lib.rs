@@ -1,5 +1,6 @@
 use tauri::Manager;
 
+pub mod cli;
 mod utils;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -17,22 +18,54 @@ pub fn run() {
         )
         .plugin(tauri_nspanel::init())
         .plugin(tauri_plugin_dialog::init())
+        .register_uri_scheme_protocol(utils::blob_store::URI_SCHEME, |ctx, request| {
+            utils::blob_store::handle_request(ctx.app_handle(), request)
+        })
         .invoke_handler(tauri::generate_handler![
             utils::nspanel::toggle_panel,
             utils::db::add_record,
             utils::db::get_records,
             utils::db::toggle_favorite,
+            utils::db::delete_records,
+            utils::db::delete_by_filter,
+            utils::db::restore_records,
+            utils::db::update_record,
+            utils::db::get_record_revisions,
+            utils::db::create_snippet,
+            utils::db::set_record_expiry,
+            utils::edit_img::edit_image,
+            utils::link_preview::get_link_preview,
+            utils::highlight::get_record_preview,
             utils::clipboard_write::choose,
+            utils::settings::get_settings,
+            utils::settings::update_settings,
+            utils::sync::sync_now,
+            utils::diagnostics::create_diagnostics_bundle,
+            utils::statistics::get_statistics,
+            utils::maintenance::run_maintenance,
+            utils::pause::get_capture_status,
+            utils::pause::pause_capture,
+            utils::pause::resume_capture,
         ])
         .setup(|app: &mut tauri::App| {
             // 隐藏dock icon
             hide_dock_icon(app);
 
-            utils::db::init(&app);
+            utils::settings::init(&app)?;
+            if let Err(e) = utils::logger::init(&app) {
+                eprintln!("failed to init logger: {}", e);
+            }
+            let db = utils::db::init(&app)?;
+            utils::expiry::init(&app);
             utils::nspanel::init(&app);
             utils::global_shortcut::register(&app);
-            utils::clipboard_read::init();
-            utils::tray::init(&app);
+            let tasks = utils::tasks::Tasks::start(&db);
+            app.manage(tasks.clone());
+            let images = utils::img_worker::init(db.clone(), tasks.clone());
+            utils::clipboard_read::init(db, images, tasks);
+            utils::tray::init(&app)?;
+            utils::sync::init(&app);
+            utils::api::init(&app);
 
             // debug(&app);
             Ok(())
@@ -2,5 +2,9 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 fn main() {
+    // 带子命令时作为命令行工具运行, 不启动界面
+    if let Some(code) = clippy_lib::cli::run(std::env::args().skip(1).collect()) {
+        std::process::exit(code);
+    }
     clippy_lib::run()
 }
@@ -0,0 +1,465 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+use tauri::Manager;
+
+use crate::utils::db::{self, Database};
+use crate::utils::error::{Error, Result};
+use crate::utils::tasks::Tasks;
+use crate::utils::{clipboard_write, optimize_img::optimize_img, settings};
+
+// 本地 API: 通过 Unix domain socket 通信, 每行一个 JSON 请求, 返回一行 JSON 响应
+// 请求: {"token": "...", "method": "list", "params": {...}}
+// 响应: {"ok": true, "data": ...} 或 {"ok": false, "code": "...", "error": "..."}
+
+const SOCKET_FILE: &str = "clippy2.sock";
+// 同时处理的连接数, 超过时直接关闭新的连接
+const MAX_CONNECTIONS: usize = 8;
+// 连接空闲超过这个时间后关闭, 避免不断开的客户端占满连接数
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct Request {
+    token: String,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Debug, Serialize)]
+struct Response {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IdParams {
+    id: i64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChooseParams {
+    id: i64,
+    #[serde(default)]
+    as_file: bool,
+}
+
+#[derive(Debug, Deserialize)]
+struct SearchParams {
+    keyword: String,
+    #[serde(default = "default_limit")]
+    limit: u32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AddParams {
+    #[serde(default = "default_record_type")]
+    record_type: String,
+    value: String,
+}
+
+fn default_limit() -> u32 {
+    db::DEFAULT_PAGE_SIZE
+}
+
+fn default_record_type() -> String {
+    "text".to_string()
+}
+
+pub fn init(app: &tauri::App) {
+    let api_settings = settings::get().api;
+    if !api_settings.enabled {
+        return;
+    }
+
+    let app_dir = match app.app_handle().path().app_data_dir() {
+        Ok(app_dir) => app_dir,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to resolve app data dir for api");
+            return;
+        }
+    };
+    let listener = match bind(&app_dir.join("api")) {
+        Ok(listener) => listener,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to bind api socket");
+            return;
+        }
+    };
+
+    let db = app.state::<Database>().inner().clone();
+    let tasks = app.state::<Tasks>().inner().clone();
+    std::thread::spawn(move || serve(&db, &tasks, listener, || settings::get().api.token));
+}
+
+// socket 放在只有当前用户能访问的目录中, 从创建开始其他用户就无法连接, socket 本身再设为 0600
+// 权限设置失败时不提供 API
+fn bind(dir: &Path) -> Result<UnixListener> {
+    std::fs::DirBuilder::new()
+        .recursive(true)
+        .mode(0o700)
+        .create(dir)?;
+    // 目录已经存在时 mode 不生效
+    std::fs::set_permissions(dir, std::fs::Permissions::from_mode(0o700))?;
+
+    let socket_path = dir.join(SOCKET_FILE);
+    // 上次退出时可能残留 socket 文件
+    let _ = std::fs::remove_file(&socket_path);
+    let listener = UnixListener::bind(&socket_path)?;
+    std::fs::set_permissions(&socket_path, std::fs::Permissions::from_mode(0o600))?;
+
+    tracing::info!(path = %socket_path.display(), "api listening");
+    Ok(listener)
+}
+
+// 连接结束(包括处理线程 panic)时释放占用的连接数
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// 每个连接一个线程, token 在每次请求时读取, 修改后立即生效
+fn serve(db: &Database, tasks: &Tasks, listener: UnixListener, token: fn() -> String) {
+    let connections = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming().flatten() {
+        let slot = ConnectionSlot(connections.clone());
+        if connections.fetch_add(1, Ordering::SeqCst) >= MAX_CONNECTIONS {
+            tracing::warn!("too many api connections, closing the new one");
+            continue;
+        }
+        let db = db.clone();
+        let tasks = tasks.clone();
+        std::thread::spawn(move || {
+            let _slot = slot;
+            handle_connection(&db, &tasks, stream, token);
+        });
+    }
+}
+
+fn handle_connection(db: &Database, tasks: &Tasks, stream: UnixStream, token: fn() -> String) {
+    let _ = stream.set_read_timeout(Some(IDLE_TIMEOUT));
+    let Ok(mut writer) = stream.try_clone() else {
+        return;
+    };
+    let reader = BufReader::new(stream);
+
+    for line in reader.lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match handle_line(db, tasks, &line, &token()) {
+            Ok(data) => Response {
+                ok: true,
+                data: Some(data),
+                code: None,
+                error: None,
+            },
+            Err(e) => Response {
+                ok: false,
+                data: None,
+                code: Some(e.code()),
+                error: Some(e.to_string()),
+            },
+        };
+
+        let mut output = serde_json::to_string(&response).unwrap_or_default();
+        output.push('\n');
+        if writer.write_all(output.as_bytes()).is_err() {
+            break;
+        }
+    }
+}
+
+fn handle_line(db: &Database, tasks: &Tasks, line: &str, expected_token: &str) -> Result<Value> {
+    let request: Request = serde_json::from_str(line)?;
+    check_token(expected_token, &request.token)?;
+    dispatch(db, tasks, &request.method, request.params)
+}
+
+// 没有配置 token 时拒绝所有请求, 不能用空 token 访问
+// 按固定时间比较, 避免通过响应时间逐个字符猜出 token
+fn check_token(expected: &str, token: &str) -> Result<()> {
+    if expected.is_empty() || !bool::from(expected.as_bytes().ct_eq(token.as_bytes())) {
+        return Err(Error::InvalidInput("Invalid token".to_string()));
+    }
+    Ok(())
+}
+
+fn parse<T: for<'de> Deserialize<'de>>(params: Value) -> Result<T> {
+    serde_json::from_value(params)
+        .map_err(|e| Error::InvalidInput(format!("Invalid params: {}", e)))
+}
+
+fn to_value<T: Serialize>(data: T) -> Result<Value> {
+    Ok(serde_json::to_value(data)?)
+}
+
+fn dispatch(db: &Database, tasks: &Tasks, method: &str, params: Value) -> Result<Value> {
+    match method {
+        "list" => {
+            let params: db::QueryParams = parse(params)?;
+            to_value(db.get_records(params)?)
+        }
+        "search" => {
+            let params: SearchParams = parse(params)?;
+            let page = db.get_records(db::QueryParams {
+                limit: params.limit,
+                keyword: Some(params.keyword),
+                ..Default::default()
+            })?;
+            to_value(page.records)
+        }
+        "get" => {
+            let params: IdParams = parse(params)?;
+            to_value(db.get_record(params.id)?)
+        }
+        "add" => {
+            let params: AddParams = parse(params)?;
+            let record = build_record_input(db, params)?;
+            let is_image = record.record_type == "image";
+            let text = (record.record_type == "text").then(|| record.value.clone());
+            let id = db.add_record(record)?;
+            if is_image {
+                tasks.recognize_text(id);
+            }
+            if let Some(text) = text {
+                tasks.text_added(id, &text);
+            }
+            to_value(db.get_record(id)?)
+        }
+        "favorite" => {
+            let params: IdParams = parse(params)?;
+            db.toggle_favorite(params.id)?;
+            to_value(db.get_record(params.id)?)
+        }
+        "choose" => {
+            let params: ChooseParams = parse(params)?;
+            let record = db.get_record(params.id)?;
+            clipboard_write::write_record(
+                db,
+                clipboard_write::RecordInput {
+                    id: record.id,
+                    record_type: record.record_type,
+                    value: record.value,
+                    as_file: params.as_file,
+                },
+            )?;
+            Ok(Value::Null)
+        }
+        _ => Err(Error::InvalidInput(format!("Unknown method: {}", method))),
+    }
+}
+
+fn build_record_input(db: &Database, params: AddParams) -> Result<db::RecordInput> {
+    match params.record_type.as_str() {
+        "text" => Ok(db::RecordInput {
+            record_type: params.record_type,
+            value: params.value,
+            thumbnail_key: None,
+            preview_key: None,
+            size: None,
+            img_size: None,
+            phash: None,
+            source_app: None,
+        }),
+        "image" => {
+            let img_bytes = STANDARD.decode(&params.value)?;
+            let optimized = optimize_img(db.blobs(), &img_bytes)?;
+            Ok(db::RecordInput {
+                record_type: params.record_type,
+                value: params.value,
+                thumbnail_key: Some(optimized.thumbnail_key),
+                preview_key: Some(optimized.preview_key),
+                size: None,
+                img_size: Some(optimized.img_size),
+                phash: Some(optimized.phash as i64),
+                source_app: None,
+            })
+        }
+        "file" => {
+            let metadata = std::fs::metadata(&params.value)?;
+            Ok(db::RecordInput {
+                record_type: params.record_type,
+                value: params.value,
+                thumbnail_key: None,
+                preview_key: None,
+                size: Some(metadata.len()),
+                img_size: None,
+                phash: None,
+                source_app: None,
+            })
+        }
+        _ => Err(Error::Unsupported(format!(
+            "Unsupported record type: {}",
+            params.record_type
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_token_is_rejected() {
+        assert!(check_token("secret", "secret").is_ok());
+        assert!(check_token("secret", "other").is_err());
+        assert!(check_token("secret", "").is_err());
+        assert!(check_token("", "").is_err());
+    }
+
+    const TOKEN: &str = "secret";
+
+    struct Server {
+        dir: tempfile::TempDir,
+        db: Database,
+    }
+
+    impl Server {
+        fn start() -> Server {
+            let dir = tempfile::tempdir().unwrap();
+            let db = db::init_at(dir.path()).unwrap();
+            let listener = bind(&dir.path().join("api")).unwrap();
+            let server_db = db.clone();
+            std::thread::spawn(move || {
+                serve(&server_db, &Tasks::detached(), listener, || {
+                    TOKEN.to_string()
+                })
+            });
+            Server { dir, db }
+        }
+
+        fn socket_path(&self) -> std::path::PathBuf {
+            self.dir.path().join("api").join(SOCKET_FILE)
+        }
+
+        fn connect(&self) -> Client {
+            Client(BufReader::new(
+                UnixStream::connect(self.socket_path()).unwrap(),
+            ))
+        }
+    }
+
+    struct Client(BufReader<UnixStream>);
+
+    impl Client {
+        // 连接被关闭时返回 None
+        fn send(&mut self, token: &str, method: &str, params: Value) -> Option<Value> {
+            let request = serde_json::json!({ "token": token, "method": method, "params": params });
+            writeln!(self.0.get_mut(), "{}", request).ok()?;
+            let mut line = String::new();
+            match self.0.read_line(&mut line) {
+                Ok(0) | Err(_) => None,
+                Ok(_) => Some(serde_json::from_str(&line).unwrap()),
+            }
+        }
+
+        fn call(&mut self, method: &str, params: Value) -> Value {
+            let response = self.send(TOKEN, method, params).unwrap();
+            assert_eq!(response["ok"], true, "{}", response);
+            response["data"].clone()
+        }
+    }
+
+    #[test]
+    fn requests_round_trip() {
+        let server = Server::start();
+        let mut client = server.connect();
+
+        let added = client.call("add", serde_json::json!({ "value": "hello api" }));
+        let id = added["id"].as_i64().unwrap();
+        assert_eq!(added["value"], "hello api");
+        assert_eq!(added["record_type"], "text");
+
+        let page = client.call("list", serde_json::json!({}));
+        assert_eq!(page["records"][0]["id"], id);
+        let found = client.call("search", serde_json::json!({ "keyword": "hello" }));
+        assert_eq!(found.as_array().unwrap().len(), 1);
+        assert_eq!(
+            client.call("get", serde_json::json!({ "id": id }))["value"],
+            "hello api"
+        );
+        assert_eq!(
+            client.call("favorite", serde_json::json!({ "id": id }))["favorite"],
+            true
+        );
+        assert_eq!(
+            client.call("choose", serde_json::json!({ "id": id })),
+            Value::Null
+        );
+
+        let missing = client
+            .send(TOKEN, "get", serde_json::json!({ "id": id + 1 }))
+            .unwrap();
+        assert_eq!(missing["ok"], false);
+        assert_eq!(missing["code"], "record_not_found");
+        let unknown = client.send(TOKEN, "drop", Value::Null).unwrap();
+        assert_eq!(unknown["code"], "invalid_input");
+        assert_eq!(server.db.get_record(id).unwrap().value, "hello api");
+    }
+
+    #[test]
+    fn wrong_token_is_rejected() {
+        let server = Server::start();
+        let mut client = server.connect();
+        let response = client.send("wrong", "list", serde_json::json!({})).unwrap();
+        assert_eq!(response["ok"], false);
+        assert_eq!(response["code"], "invalid_input");
+        assert!(response.get("data").is_none());
+        // 连接仍然可用
+        client.call("list", serde_json::json!({}));
+    }
+
+    #[test]
+    fn socket_is_private() {
+        let server = Server::start();
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+        assert_eq!(mode(&server.dir.path().join("api")), 0o700);
+        assert_eq!(mode(&server.socket_path()), 0o600);
+    }
+
+    #[test]
+    fn connections_are_limited() {
+        let server = Server::start();
+        let mut clients: Vec<Client> = (0..MAX_CONNECTIONS).map(|_| server.connect()).collect();
+        for client in &mut clients {
+            client.call("list", serde_json::json!({}));
+        }
+        assert!(server
+            .connect()
+            .send(TOKEN, "list", serde_json::json!({}))
+            .is_none());
+
+        // 断开一个连接后可以重新连接
+        clients.pop();
+        let reconnected = (0..100).any(|_| {
+            std::thread::sleep(Duration::from_millis(20));
+            server
+                .connect()
+                .send(TOKEN, "list", serde_json::json!({}))
+                .is_some()
+        });
+        assert!(reconnected);
+    }
+}
@@ -0,0 +1,182 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{AppHandle, Manager, Runtime};
+
+use crate::utils::error::{Error, Result};
+
+// 按内容寻址的文件存储, 用于存放缩略图等二进制数据, 通过自定义协议提供给前端
+// key 为 `<sha256>.<扩展名>`, 文件存放在 `blobs/<key 前两位>/<key>`
+pub const URI_SCHEME: &str = "clippy";
+
+// 数据目录下的 blobs 目录, 由数据库持有, 同时作为 tauri 的 managed state 供自定义协议使用
+#[derive(Clone)]
+pub struct BlobStore {
+    dir: PathBuf,
+}
+
+impl BlobStore {
+    pub fn open(app_dir: &Path) -> Result<BlobStore> {
+        let dir = app_dir.join("blobs");
+        std::fs::create_dir_all(&dir)?;
+        Ok(BlobStore { dir })
+    }
+
+    // 只接受 put 生成的 key, 防止通过 `..` 等读取其他文件
+    fn path(&self, key: &str) -> Result<PathBuf> {
+        let (hash, ext) = key
+            .split_once('.')
+            .ok_or_else(|| Error::InvalidInput(format!("Invalid blob key: {}", key)))?;
+        let valid = hash.len() == 64
+            && hash.chars().all(|c| c.is_ascii_hexdigit())
+            && !ext.is_empty()
+            && ext.chars().all(|c| c.is_ascii_alphanumeric());
+        if !valid {
+            return Err(Error::InvalidInput(format!("Invalid blob key: {}", key)));
+        }
+        Ok(self.dir.join(&hash[..2]).join(key))
+    }
+
+    // 写入数据并返回 key, 相同内容只保存一份
+    pub fn put(&self, bytes: &[u8], ext: &str) -> Result<String> {
+        let hash: String = Sha256::digest(bytes)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        let key = format!("{}.{}", hash, ext);
+        let path = self.path(&key)?;
+        if path.exists() {
+            // 刷新修改时间, 避免在入库前被清理
+            let _ = std::fs::File::options()
+                .append(true)
+                .open(&path)
+                .and_then(|f| f.set_modified(SystemTime::now()));
+            return Ok(key);
+        }
+
+        let parent = path.parent().unwrap();
+        std::fs::create_dir_all(parent)?;
+        // 先写到名字唯一的临时文件再重命名, 同时写入同一内容时互不干扰, 也不会读到写了一半的文件
+        // 异常退出留下的临时文件由 gc 清理
+        let mut file = tempfile::NamedTempFile::new_in(parent)?;
+        file.write_all(bytes)?;
+        file.persist(&path).map_err(|e| e.error)?;
+        Ok(key)
+    }
+
+    pub fn read(&self, key: &str) -> Result<Vec<u8>> {
+        Ok(std::fs::read(self.path(key)?)?)
+    }
+
+    // 文件大小, 文件不存在或 key 无效时返回 None
+    pub fn size(&self, key: &str) -> Option<u64> {
+        let path = self.path(key).ok()?;
+        std::fs::metadata(path).ok().map(|m| m.len())
+    }
+
+    // 删除不再被引用的文件, 返回删除的数量
+    pub fn gc(&self, referenced: &HashSet<String>) -> Result<usize> {
+        let mut removed = 0;
+        let shards = std::fs::read_dir(&self.dir)?;
+        for shard in shards.filter_map(|entry| entry.ok()) {
+            let Ok(files) = std::fs::read_dir(shard.path()) else {
+                continue;
+            };
+            for file in files.filter_map(|entry| entry.ok()) {
+                let name = file.file_name().to_string_lossy().into_owned();
+                let age = file
+                    .metadata()
+                    .and_then(|m| m.modified())
+                    .ok()
+                    .and_then(|t| SystemTime::now().duration_since(t).ok());
+                let is_recent = !matches!(age, Some(age) if age >= GC_GRACE_PERIOD);
+                if is_recent || referenced.contains(&name) {
+                    continue;
+                }
+                if std::fs::remove_file(file.path()).is_ok() {
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+}
+
+// 刚写入的文件可能还没来得及入库, 清理时跳过
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+fn content_type(key: &str) -> &'static str {
+    match key.rsplit('.').next() {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        Some("avif") => "image/avif",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("ico") => "image/x-icon",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    }
+}
+
+// 自定义协议的处理函数, 前端通过 convertFileSrc(key, "clippy") 访问
+pub fn handle_request<R: Runtime>(
+    app: &AppHandle<R>,
+    request: Request<Vec<u8>>,
+) -> Response<Vec<u8>> {
+    let key = request.uri().path().trim_start_matches('/');
+    // 数据库初始化之前还没有 blob store
+    let bytes = app
+        .try_state::<BlobStore>()
+        .ok_or_else(|| Error::Other("Blob store not initialized".to_string()))
+        .and_then(|blobs| blobs.read(key));
+    match bytes {
+        Ok(bytes) => Response::builder()
+            .header(header::CONTENT_TYPE, content_type(key))
+            // key 由内容决定, 内容不会变化
+            .header(header::CACHE_CONTROL, "max-age=31536000, immutable")
+            .body(bytes)
+            .unwrap(),
+        Err(_) => Response::builder()
+            .status(StatusCode::NOT_FOUND)
+            .body(Vec::new())
+            .unwrap(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn put_and_read() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::open(dir.path()).unwrap();
+        let key = blobs.put(b"hello", "png").unwrap();
+        assert_eq!(blobs.read(&key).unwrap(), b"hello");
+        assert_eq!(blobs.size(&key), Some(5));
+        assert_eq!(blobs.put(b"hello", "png").unwrap(), key);
+        assert!(blobs.read("../app.db").is_err());
+    }
+
+    #[test]
+    fn concurrent_puts_do_not_conflict() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::open(dir.path()).unwrap();
+        let bytes = vec![7u8; 256 * 1024];
+        let keys: Vec<String> = std::thread::scope(|s| {
+            let handles: Vec<_> = (0..8)
+                .map(|_| s.spawn(|| blobs.put(&bytes, "png").unwrap()))
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        assert!(keys.iter().all(|key| *key == keys[0]));
+        assert_eq!(blobs.read(&keys[0]).unwrap(), bytes);
+
+        // 临时文件都已经重命名或删除, 目录中只剩一个文件
+        let shard = dir.path().join("blobs").join(&keys[0][..2]);
+        assert_eq!(std::fs::read_dir(shard).unwrap().count(), 1);
+    }
+}
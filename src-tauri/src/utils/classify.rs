@@ -0,0 +1,495 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+
+// 文本记录的子类型, 入库时识别, 用于前端展示和筛选
+pub const PLAIN: &str = "plain";
+pub const URL: &str = "url";
+pub const EMAIL: &str = "email";
+pub const PHONE: &str = "phone";
+pub const COLOR: &str = "color";
+pub const JSON: &str = "json";
+pub const CODE: &str = "code";
+pub const PATH: &str = "path";
+pub const NUMBER: &str = "number";
+pub const DATE: &str = "date";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Classification {
+    pub subtype: &'static str,
+    // 代码的语言, 识别不出时为 None
+    pub language: Option<&'static str>,
+}
+
+impl Classification {
+    fn new(subtype: &'static str) -> Self {
+        Classification {
+            subtype,
+            language: None,
+        }
+    }
+}
+
+lazy_static! {
+    static ref URL_RE: Regex =
+        Regex::new(r"^(?i)((https?|ftp)://[^\s/$.?#][^\s]*|www\.[^\s/]+\.[a-z]{2,}[^\s]*)$").unwrap();
+    static ref EMAIL_RE: Regex =
+        Regex::new(r"^(?i)(mailto:)?[a-z0-9._%+-]+@[a-z0-9-]+(\.[a-z0-9-]+)*\.[a-z]{2,}$").unwrap();
+    static ref HEX_COLOR_RE: Regex =
+        Regex::new(r"^#?([0-9a-fA-F]{3}|[0-9a-fA-F]{4}|[0-9a-fA-F]{6}|[0-9a-fA-F]{8})$").unwrap();
+    static ref FUNC_COLOR_RE: Regex = Regex::new(
+        r"^(?i)(rgba?|hsla?)\(\s*\d{1,3}(\.\d+)?%?\s*(,\s*|\s+)\d{1,3}(\.\d+)?%?\s*(,\s*|\s+)\d{1,3}(\.\d+)?%?\s*([,/]\s*(0|1|0?\.\d+|\d{1,3}%)\s*)?\)$"
+    )
+    .unwrap();
+    static ref NUMBER_RE: Regex =
+        Regex::new(r"^[+-]?(\d{1,3}(,\d{3})+|\d+)(\.\d+)?([eE][+-]?\d+)?%?$|^0[xX][0-9a-fA-F]+$").unwrap();
+    static ref CN_MOBILE_RE: Regex = Regex::new(r"^(\+?86[\s-]?)?1[3-9]\d[\s-]?\d{4}[\s-]?\d{4}$").unwrap();
+    static ref PHONE_RE: Regex = Regex::new(r"^\+?\(?\d{1,4}\)?([\s.-]?\(?\d{1,4}\)?){1,5}$").unwrap();
+    static ref DATE_RE: Regex = Regex::new(
+        r"^\d{4}[-/.]\d{1,2}[-/.]\d{1,2}([ T]\d{1,2}:\d{2}(:\d{2}(\.\d+)?)?\s*(Z|[+-]\d{2}:?\d{2})?)?$|^\d{4}年\d{1,2}月\d{1,2}日$|^\d{1,2}/\d{1,2}/\d{4}$"
+    )
+    .unwrap();
+    static ref PATH_RE: Regex = Regex::new(r"^(~?/|[a-zA-Z]:\\)[^\n\x00]*$").unwrap();
+}
+
+// 识别代码语言的特征, 命中的越多越可能是该语言
+const LANGUAGE_HINTS: &[(&str, &[&str])] = &[
+    (
+        "rust",
+        &[
+            "fn ", "let mut ", "impl ", "pub fn", "::", "-> ", "match ", "use std", "#[derive",
+            "&self", "Vec<", "Option<", "unwrap()",
+        ],
+    ),
+    (
+        "python",
+        &[
+            "def ", "import ", "from ", "self.", "elif ", "print(", "__init__", "None", "True",
+            "lambda ", "):\n",
+        ],
+    ),
+    (
+        "typescript",
+        &[
+            "interface ",
+            ": string",
+            ": number",
+            "export ",
+            "import {",
+            "=> ",
+            "const ",
+            "type ",
+            "<T>",
+            "as const",
+        ],
+    ),
+    (
+        "javascript",
+        &[
+            "function ",
+            "const ",
+            "let ",
+            "=> ",
+            "console.log",
+            "require(",
+            "document.",
+            "module.exports",
+            "===",
+            "undefined",
+        ],
+    ),
+    (
+        "go",
+        &[
+            "func ",
+            "package ",
+            ":= ",
+            "fmt.",
+            "go ",
+            "chan ",
+            "defer ",
+            "err != nil",
+            "struct {",
+        ],
+    ),
+    (
+        "java",
+        &[
+            "public class",
+            "private ",
+            "public static void",
+            "System.out",
+            "new ",
+            "@Override",
+            "extends ",
+            "implements ",
+            "import java",
+        ],
+    ),
+    (
+        "c",
+        &[
+            "#include", "int main", "printf(", "malloc(", "->", "sizeof(", "NULL", "void ",
+        ],
+    ),
+    (
+        "cpp",
+        &[
+            "#include",
+            "std::",
+            "cout",
+            "template<",
+            "namespace ",
+            "nullptr",
+            "class ",
+            "public:",
+        ],
+    ),
+    (
+        "shell",
+        &[
+            "#!/bin/", "echo ", "sudo ", "export ", "$(", "| grep", "fi\n", "then\n", "&& ", "cd ",
+        ],
+    ),
+    (
+        "sql",
+        &[
+            "SELECT ",
+            "FROM ",
+            "WHERE ",
+            "INSERT INTO",
+            "UPDATE ",
+            "CREATE TABLE",
+            "JOIN ",
+            "GROUP BY",
+            "ORDER BY",
+        ],
+    ),
+    (
+        "html",
+        &[
+            "<div",
+            "</",
+            "<html",
+            "<span",
+            "<a ",
+            "class=\"",
+            "<p>",
+            "<!DOCTYPE",
+        ],
+    ),
+    (
+        "css",
+        &[
+            "{\n",
+            "px;",
+            "color:",
+            "margin:",
+            "padding:",
+            "display:",
+            "@media",
+            "font-size:",
+        ],
+    ),
+];
+
+// 至少命中的特征数
+const MIN_LANGUAGE_SCORE: usize = 2;
+
+pub fn classify(text: &str) -> Classification {
+    let trimmed = text.trim();
+    if trimmed.is_empty() {
+        return Classification::new(PLAIN);
+    }
+
+    if !trimmed.contains('\n') {
+        if let Some(subtype) = classify_line(trimmed) {
+            return Classification::new(subtype);
+        }
+    }
+
+    if is_json(trimmed) {
+        return Classification::new(JSON);
+    }
+
+    if let Some(language) = detect_code(trimmed) {
+        return Classification {
+            subtype: CODE,
+            language,
+        };
+    }
+
+    Classification::new(PLAIN)
+}
+
+// 单行文本的识别, 顺序决定优先级
+fn classify_line(line: &str) -> Option<&'static str> {
+    if URL_RE.is_match(line) {
+        return Some(URL);
+    }
+    if EMAIL_RE.is_match(line) {
+        return Some(EMAIL);
+    }
+    if DATE_RE.is_match(line) {
+        return Some(DATE);
+    }
+    if is_phone(line) {
+        return Some(PHONE);
+    }
+    // 1e10 这类数字也符合颜色的格式, 先判断数字
+    if NUMBER_RE.is_match(line) {
+        return Some(NUMBER);
+    }
+    if is_color(line) {
+        return Some(COLOR);
+    }
+    if is_path(line) {
+        return Some(PATH);
+    }
+    None
+}
+
+// 不带 # 的纯数字或纯字母更可能是数字或单词, 不算颜色
+fn is_color(line: &str) -> bool {
+    if FUNC_COLOR_RE.is_match(line) {
+        return true;
+    }
+    if !HEX_COLOR_RE.is_match(line) {
+        return false;
+    }
+    line.starts_with('#')
+        || (line.chars().any(|c| c.is_ascii_digit())
+            && line.chars().any(|c| c.is_ascii_alphabetic()))
+}
+
+// 纯数字只有符合手机号格式才算电话, 其他情况需要有 + 或分隔符
+fn is_phone(line: &str) -> bool {
+    if CN_MOBILE_RE.is_match(line) {
+        return true;
+    }
+    if !PHONE_RE.is_match(line) {
+        return false;
+    }
+    let digits = line.chars().filter(|c| c.is_ascii_digit()).count();
+    let has_separator = line.starts_with('+') || line.contains([' ', '-', '(']);
+    (7..=15).contains(&digits) && has_separator && !line.contains("..")
+}
+
+fn is_path(line: &str) -> bool {
+    if !PATH_RE.is_match(line) || line.contains("://") {
+        return false;
+    }
+    // 至少包含一级目录或文件名
+    let rest = line.trim_start_matches('~').trim_start_matches('/');
+    !rest.is_empty() && !rest.starts_with('/')
+}
+
+fn is_json(text: &str) -> bool {
+    let is_container = (text.starts_with('{') && text.ends_with('}'))
+        || (text.starts_with('[') && text.ends_with(']'));
+    is_container && serde_json::from_str::<serde_json::Value>(text).is_ok()
+}
+
+// 先判断结构上像不像代码, 再按特征给各语言打分
+// 不是代码时返回 None, 是代码但识别不出语言时返回 Some(None)
+fn detect_code(text: &str) -> Option<Option<&'static str>> {
+    let lines: Vec<&str> = text.lines().filter(|l| !l.trim().is_empty()).collect();
+    let code_lines = lines
+        .iter()
+        .filter(|l| {
+            let l = l.trim_end();
+            l.ends_with(';')
+                || l.ends_with('{')
+                || l.ends_with('}')
+                || l.ends_with(':')
+                || l.ends_with(')')
+                || l.ends_with('>')
+                || l.starts_with("    ")
+                || l.starts_with('\t')
+                || l.trim_start().starts_with("//")
+                || l.trim_start().starts_with('#')
+        })
+        .count();
+    // 多行时至少一半的行像代码, 单行时必须以代码的结尾符结束
+    let looks_like_code = if lines.len() > 1 {
+        code_lines * 2 >= lines.len()
+    } else {
+        code_lines == 1
+    };
+    if !looks_like_code {
+        return None;
+    }
+
+    match detect_language(text) {
+        Some(language) => Some(Some(language)),
+        // 单行的结构特征太弱(如括号结尾的句子), 识别不出语言时不算代码
+        None if lines.len() > 1 => Some(None),
+        None => None,
+    }
+}
+
+fn detect_language(text: &str) -> Option<&'static str> {
+    LANGUAGE_HINTS
+        .iter()
+        .map(|(language, hints)| {
+            let score = hints.iter().filter(|hint| text.contains(*hint)).count();
+            (*language, score)
+        })
+        .filter(|(_, score)| *score >= MIN_LANGUAGE_SCORE)
+        // 分数相同时取靠前的语言
+        .fold(
+            None,
+            |best: Option<(&str, usize)>, (language, score)| match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((language, score)),
+            },
+        )
+        .map(|(language, _)| language)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subtype(text: &str) -> &'static str {
+        classify(text).subtype
+    }
+
+    #[test]
+    fn empty_and_plain() {
+        assert_eq!(subtype(""), PLAIN);
+        assert_eq!(subtype("  \n "), PLAIN);
+        assert_eq!(subtype("hello world"), PLAIN);
+        assert_eq!(subtype("今天天气不错 (晴)"), PLAIN);
+        assert_eq!(subtype("第一行\n第二行\n第三行"), PLAIN);
+    }
+
+    #[test]
+    fn urls() {
+        assert_eq!(subtype("https://example.com/a?b=1"), URL);
+        assert_eq!(subtype("  http://example.com  "), URL);
+        assert_eq!(subtype("ftp://files.example.com/x.zip"), URL);
+        assert_eq!(subtype("www.example.com/path"), URL);
+        assert_eq!(subtype("https://example.com has spaces"), PLAIN);
+    }
+
+    #[test]
+    fn emails() {
+        assert_eq!(subtype("a.b+tag@example.co.uk"), EMAIL);
+        assert_eq!(subtype("mailto:someone@example.com"), EMAIL);
+        assert_eq!(subtype("someone@localhost"), PLAIN);
+    }
+
+    #[test]
+    fn dates() {
+        assert_eq!(subtype("2024-01-02"), DATE);
+        assert_eq!(subtype("2024/1/2"), DATE);
+        assert_eq!(subtype("2024-01-02T10:20:30.123Z"), DATE);
+        assert_eq!(subtype("2024-01-02 10:20 +08:00"), DATE);
+        assert_eq!(subtype("2024年1月2日"), DATE);
+        assert_eq!(subtype("01/02/2024"), DATE);
+    }
+
+    #[test]
+    fn phones() {
+        assert_eq!(subtype("13812345678"), PHONE);
+        assert_eq!(subtype("138 1234 5678"), PHONE);
+        assert_eq!(subtype("138-1234-5678"), PHONE);
+        assert_eq!(subtype("+86 13812345678"), PHONE);
+        assert_eq!(subtype("+8613812345678"), PHONE);
+        assert_eq!(subtype("+1 (555) 123-4567"), PHONE);
+        assert_eq!(subtype("010-12345678"), PHONE);
+        // 2 开头的 11 位数字不是手机号
+        assert_eq!(subtype("23812345678"), NUMBER);
+    }
+
+    #[test]
+    fn numbers() {
+        // 纯数字没有分隔符, 不算电话
+        assert_eq!(subtype("123456"), NUMBER);
+        assert_eq!(subtype("-12.5"), NUMBER);
+        assert_eq!(subtype("1,234,567.89"), NUMBER);
+        assert_eq!(subtype("50%"), NUMBER);
+        assert_eq!(subtype("0xFF"), NUMBER);
+        // 也符合不带 # 的十六进制颜色格式
+        assert_eq!(subtype("1e10"), NUMBER);
+        assert_eq!(subtype("2E-3"), NUMBER);
+    }
+
+    #[test]
+    fn ip_address_is_plain() {
+        assert_eq!(subtype("192.168.1.1"), PLAIN);
+        assert_eq!(subtype("10.0.0.1"), PLAIN);
+    }
+
+    #[test]
+    fn colors() {
+        assert_eq!(subtype("#fff"), COLOR);
+        assert_eq!(subtype("#FF000080"), COLOR);
+        assert_eq!(subtype("ff00aa"), COLOR);
+        assert_eq!(subtype("1e1e1e"), COLOR);
+        assert_eq!(subtype("rgb(255, 0, 0)"), COLOR);
+        assert_eq!(subtype("rgba(0,0,0,0.5)"), COLOR);
+        assert_eq!(subtype("hsl(120 50% 50% / 50%)"), COLOR);
+        // 纯字母是单词
+        assert_eq!(subtype("facade"), PLAIN);
+    }
+
+    #[test]
+    fn paths() {
+        assert_eq!(subtype("~/x"), PATH);
+        assert_eq!(subtype("/usr/local/bin"), PATH);
+        assert_eq!(subtype("C:\\Users\\me"), PATH);
+        assert_eq!(subtype("/"), PLAIN);
+        assert_eq!(subtype("~/"), PLAIN);
+        assert_eq!(subtype("https://example.com/x"), URL);
+        assert_eq!(subtype("file:///tmp/x"), PLAIN);
+    }
+
+    #[test]
+    fn json_before_code() {
+        assert_eq!(subtype(r#"{"a": 1}"#), JSON);
+        assert_eq!(subtype("[1, 2, 3]"), JSON);
+        assert_eq!(subtype("{\n  \"name\": \"x\",\n  \"list\": [1]\n}"), JSON);
+        // 像 JSON 但解析失败的按代码识别
+        let code = classify("{\n  let x = 1;\n  x + 1;\n}");
+        assert_eq!(code.subtype, CODE);
+        assert_eq!(code.language, None);
+    }
+
+    #[test]
+    fn code_languages() {
+        let rust = "pub fn main() {\n    let mut v: Vec<u8> = Vec::new();\n}";
+        assert_eq!(
+            classify(rust),
+            Classification {
+                subtype: CODE,
+                language: Some("rust")
+            }
+        );
+        let python = "def f(x):\n    return x\n\nprint(f(1))";
+        assert_eq!(classify(python).language, Some("python"));
+        let sql = "SELECT id FROM record\nWHERE id = 1;";
+        assert_eq!(classify(sql).language, Some("sql"));
+        assert_eq!(classify("echo $(date) && cd /tmp;").language, Some("shell"));
+    }
+
+    #[test]
+    fn code_without_language() {
+        let code = classify("foo {\n    bar;\n}");
+        assert_eq!(code.subtype, CODE);
+        assert_eq!(code.language, None);
+        // 单行没有语言特征时不算代码
+        assert_eq!(subtype("call me (maybe)"), PLAIN);
+    }
+
+    #[test]
+    fn language_ties_prefer_earlier_entry() {
+        // typescript 和 javascript 都只命中 "const " 和 "=> "
+        let code = "const f = (x) => x;\nconst g = f;";
+        assert_eq!(detect_language(code), Some("typescript"));
+        // c 命中 "#include" 和 "void ", cpp 命中 "#include" 和 "class "
+        let code = "#include <x.h>\nclass A;\nvoid f();";
+        assert_eq!(detect_language(code), Some("c"));
+        assert_eq!(detect_language("only one hint: fn "), None);
+    }
+}
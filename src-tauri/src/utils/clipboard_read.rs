@@ -6,21 +6,34 @@ use lazy_static::lazy_static;
 use objc::runtime::Object;
 use objc::{class, msg_send, sel, sel_impl};
 use std::fs;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicIsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
 use std::time::Duration;
 
-use crate::utils::db::{add_record, RecordInput};
-use crate::utils::optimize_img::optimize_img;
+use crate::utils::db::{Database, RecordInput};
+use crate::utils::error::Result;
+use crate::utils::img_worker::ImageQueue;
+use crate::utils::pause;
+use crate::utils::tasks::Tasks;
 
 struct Record {
     record_type: Option<String>,
     value: Option<String>,
     img_bytes: Option<Vec<u8>>,
+    // 最后一次读到这条内容时剪贴板的 changeCount
+    change_count: isize,
 }
 
 impl Record {
     // 更新记录，如果记录没有变化，则返回 false
-    fn update(&mut self, record_type: &str, value: &str, img_bytes: Option<Vec<u8>>) -> bool {
+    fn update(
+        &mut self,
+        change_count: isize,
+        record_type: &str,
+        value: &str,
+        img_bytes: Option<Vec<u8>>,
+    ) -> bool {
+        self.change_count = change_count;
         let record_type = Some(record_type.to_string());
         let value = Some(value.to_string());
 
@@ -33,6 +46,13 @@ impl Record {
         true
     }
 
+    fn update_img(&mut self, change_count: isize, img_bytes: Vec<u8>) {
+        self.change_count = change_count;
+        self.record_type = Some("image".to_string());
+        self.value = None;
+        self.img_bytes = Some(img_bytes);
+    }
+
     fn is_same_img(&self, img_bytes: &[u8]) -> bool {
         if self.img_bytes.is_none() {
             return false;
@@ -46,27 +66,61 @@ lazy_static! {
         record_type: None,
         value: None,
         img_bytes: None,
+        change_count: -1,
     });
 }
 
-pub fn init() {
-    // 每秒检查一次剪贴板变化
-    std::thread::spawn(move || {
-        let runtime = tokio::runtime::Runtime::new().unwrap(); // 创建一个新的 Tokio 运行时
-        runtime.block_on(async {
-            loop {
-                check().await; // 在异步上下文中调用
-                std::thread::sleep(Duration::from_millis(300));
-            }
-        });
+// 不记录的剪贴板变化: 从面板选择写入剪贴板(已经记为粘贴), 或暂停期间复制的内容
+static SKIP_CHANGE_COUNT: AtomicIsize = AtomicIsize::new(-1);
+
+// 轮询线程意外退出后, 等待一段时间再重启
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+pub fn init(db: Database, images: ImageQueue, tasks: Tasks) {
+    // 由守护线程启动轮询线程, 轮询线程 panic 时自动重启, 避免剪贴板监听静默停止
+    std::thread::spawn(move || loop {
+        let db = db.clone();
+        let images = images.clone();
+        let tasks = tasks.clone();
+        let poller = std::thread::Builder::new()
+            .name("clipboard-read".to_string())
+            .spawn(move || poll(&db, &images, &tasks));
+        match poller.map(|handle| handle.join()) {
+            Ok(Ok(())) => break,
+            Ok(Err(_)) => tracing::error!("clipboard polling thread panicked, restarting"),
+            Err(e) => tracing::error!(error = %e, "failed to spawn clipboard polling thread"),
+        }
+        std::thread::sleep(RESTART_DELAY);
     });
 }
 
-pub async fn check() {
+// 每 300 毫秒检查一次剪贴板变化, 单次失败只记录日志, 不中断轮询
+// 暂停时线程继续运行, 只是不读取剪贴板
+fn poll(db: &Database, images: &ImageQueue, tasks: &Tasks) {
+    loop {
+        if pause::is_paused() {
+            std::thread::sleep(Duration::from_millis(300));
+            continue;
+        }
+        if let Err(e) = check(db, images, tasks) {
+            tracing::error!(error = %e, "failed to capture clipboard");
+        }
+        std::thread::sleep(Duration::from_millis(300));
+    }
+}
+
+// 轮询线程 panic 时锁可能处于中毒状态, 记录本身仍然可用
+fn last_record() -> MutexGuard<'static, Record> {
+    LAST_RECORD.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn check(db: &Database, images: &ImageQueue, tasks: &Tasks) -> Result<()> {
     let record_to_add = unsafe {
         objc::rc::autoreleasepool(|| {
             let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
             let types: id = msg_send![pasteboard, types];
+            let change_count: isize = msg_send![pasteboard, changeCount];
+            let skip = change_count == SKIP_CHANGE_COUNT.load(Ordering::Relaxed);
 
             // 检查是否包含文件路径
             let file_url_type = NSString::alloc(nil).init_str("public.file-url");
@@ -84,15 +138,18 @@ pub async fn check() {
                 let url_string = nsstring_to_rust_string(nsstring);
                 // 把它转换为标准化的路径
                 if let Some(path) = resolve_file_url(&url_string) {
-                    if LAST_RECORD.lock().unwrap().update("file", &path, None) {
+                    if last_record().update(change_count, "file", &path, None) && !skip {
                         // 获取文件大小
                         if let Ok(metadata) = fs::metadata(path.clone()) {
                             let record = RecordInput {
                                 record_type: "file".to_string(),
                                 value: path,
-                                thumbnail: None,
+                                thumbnail_key: None,
+                                preview_key: None,
                                 size: Some(metadata.len()),
                                 img_size: None,
+                                phash: None,
+                                source_app: frontmost_app(),
                             };
                             return Some(record);
                         }
@@ -101,34 +158,34 @@ pub async fn check() {
                 return None;
             }
 
-            // 检查是否包含图片
-            let image_type = NSString::alloc(nil).init_str("public.tiff");
-            let contains_images: bool = msg_send![types, containsObject: image_type];
-            if contains_images {
+            // 检查是否包含图片, gif 优先, 保留动图的原始数据
+            for img_type in ["com.compuserve.gif", "public.tiff"] {
+                let image_type = NSString::alloc(nil).init_str(img_type);
+                let contains_images: bool = msg_send![types, containsObject: image_type];
+                if !contains_images {
+                    continue;
+                }
                 let data: id = msg_send![pasteboard, dataForType: image_type];
-                if !data.is_null() {
-                    let length: usize = msg_send![data, length];
-                    let bytes: *const u8 = msg_send![data, bytes];
-                    let slice = std::slice::from_raw_parts(bytes, length);
-                    let mut last_record = LAST_RECORD.lock().unwrap();
+                if data.is_null() {
+                    return None;
+                }
+                let length: usize = msg_send![data, length];
+                let bytes: *const u8 = msg_send![data, bytes];
+                let slice = std::slice::from_raw_parts(bytes, length);
+                {
+                    let mut last_record = last_record();
                     // 在encode前检测图片是不是同一张, 如果一样, encode没有意义
-                    if last_record.is_same_img(&slice) {
+                    if last_record.is_same_img(slice) {
+                        last_record.change_count = change_count;
                         return None;
                     }
-                    let (thumbnail, img_size) = optimize_img(&slice).unwrap();
-                    let img_base64 = STANDARD.encode(slice);
-                    let _ = last_record.update("image", &img_base64, Some(slice.to_vec()));
-
-                    let record = RecordInput {
-                        record_type: "image".to_string(),
-                        value: img_base64,
-                        thumbnail: Some(thumbnail),
-                        size: None,
-                        img_size: Some(img_size),
-                    };
-                    return Some(record);
+                    last_record.update_img(change_count, slice.to_vec());
                 }
-                return None;
+                if skip {
+                    return None;
+                }
+                // 缩略图在后台生成, 图片过大或队列已满时只保存占位记录
+                return images.enqueue(slice, frontmost_app());
             }
 
             // 检查是否包含文字
@@ -138,13 +195,16 @@ pub async fn check() {
                 let string: id = msg_send![pasteboard, stringForType: text_type];
                 if !string.is_null() {
                     let t = nsstring_to_rust_string(string);
-                    if LAST_RECORD.lock().unwrap().update("text", &t, None) {
+                    if last_record().update(change_count, "text", &t, None) && !skip {
                         let record = RecordInput {
                             record_type: "text".to_string(),
                             value: t,
-                            thumbnail: None,
+                            thumbnail_key: None,
+                            preview_key: None,
                             size: None,
                             img_size: None,
+                            phash: None,
+                            source_app: frontmost_app(),
                         };
                         return Some(record);
                     }
@@ -154,13 +214,75 @@ pub async fn check() {
         })
     };
 
-    if let Some(record) = record_to_add {
-        add_record(record).await.unwrap();
+    let Some(record) = record_to_add else {
+        return Ok(());
+    };
+    // 占位记录没有图片内容, 不需要识别文字
+    let is_image = record.record_type == "image" && !record.value.is_empty();
+    let text = (record.record_type == "text").then(|| record.value.clone());
+    let id = db.add_record(record)?;
+    tracing::debug!(id, is_image, "clipboard record captured");
+    if is_image {
+        tasks.recognize_text(id);
+    }
+    if let Some(text) = text {
+        tasks.text_added(id, &text);
+    }
+    Ok(())
+}
+
+// 剪贴板中当前是否还是这条内容, 之后复制了无法识别的内容时 changeCount 会不同
+pub fn holds(record_type: &str, value: &str) -> bool {
+    let change_count: isize = unsafe {
+        let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+        msg_send![pasteboard, changeCount]
+    };
+    let last_record = last_record();
+    if last_record.change_count != change_count
+        || last_record.record_type.as_deref() != Some(record_type)
+    {
+        return false;
+    }
+    match &last_record.img_bytes {
+        // 图片记录的 value 是原图的 base64
+        Some(img_bytes) => STANDARD.encode(img_bytes) == value,
+        None => last_record.value.as_deref() == Some(value),
+    }
+}
+
+// 让轮询线程跳过指定 changeCount 的内容, 写入剪贴板时传入 clearContents 返回的值,
+// 在写入内容之前就标记好, 轮询线程不会读到写了一半的剪贴板
+pub fn skip(change_count: isize) {
+    SKIP_CHANGE_COUNT.store(change_count, Ordering::Relaxed);
+}
+
+// 让轮询线程跳过剪贴板当前的内容, 用于无法拿到 changeCount 的写入(osascript)和恢复记录时
+pub fn skip_current() {
+    unsafe {
+        let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+        let change_count: isize = msg_send![pasteboard, changeCount];
+        skip(change_count);
+    }
+}
+
+// 当前激活的应用即复制内容的来源, 返回其 bundle id
+fn frontmost_app() -> Option<String> {
+    unsafe {
+        let workspace: id = msg_send![class!(NSWorkspace), sharedWorkspace];
+        let app: id = msg_send![workspace, frontmostApplication];
+        if app.is_null() {
+            return None;
+        }
+        let bundle_id: id = msg_send![app, bundleIdentifier];
+        if bundle_id.is_null() {
+            return None;
+        }
+        Some(nsstring_to_rust_string(bundle_id))
     }
 }
 
 /// 将 `NSString` 转换为 Rust 字符串
-fn nsstring_to_rust_string(nsstring: id) -> String {
+pub fn nsstring_to_rust_string(nsstring: id) -> String {
     unsafe {
         let c_str: *const libc::c_char = msg_send![nsstring, UTF8String];
         std::ffi::CStr::from_ptr(c_str)
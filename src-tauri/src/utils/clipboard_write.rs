@@ -1,18 +1,42 @@
-use crate::utils;
+use crate::utils::clipboard_read;
+use crate::utils::db::{self, Database};
+use crate::utils::error::{Error, Result};
+use crate::utils::optimize_img::decode_img;
+use crate::utils::settings;
 use base64::engine::general_purpose::STANDARD;
 use base64::Engine;
 use cocoa::base::{id, nil};
 use cocoa::foundation::NSString;
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat};
+use lazy_static::lazy_static;
 use objc::{class, msg_send, sel, sel_impl};
 use serde::Deserialize;
-use std::path::Path;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::{Arc, Mutex};
+use tauri::State;
 
-fn write_text(text: String) -> Result<(), String> {
+type Representations = Arc<Vec<(&'static str, Vec<u8>)>>;
+
+lazy_static! {
+    // 最近一次粘贴的图片(内容哈希和粘贴设置)转换出的各种格式, 同一张图片反复粘贴时不再重新编码
+    static ref LAST_IMAGE: Mutex<Option<(String, settings::PasteSettings, Representations)>> =
+        Mutex::new(None);
+}
+
+// 清空剪贴板, 并在写入内容之前让轮询线程跳过这次变化
+unsafe fn clear_for_write(pasteboard: id) {
+    let change_count: isize = msg_send![pasteboard, clearContents];
+    clipboard_read::skip(change_count);
+}
+
+fn write_text(text: String) -> Result<()> {
     unsafe {
         objc::rc::autoreleasepool(|| {
             let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
-            let _: () = msg_send![pasteboard, clearContents];
+            clear_for_write(pasteboard);
 
             let text_type = NSString::alloc(nil).init_str("public.utf8-plain-text");
             let nsstring = NSString::alloc(nil).init_str(&text);
@@ -21,68 +45,202 @@ fn write_text(text: String) -> Result<(), String> {
             if success {
                 Ok(())
             } else {
-                Err("Failed to write text to clipboard".to_string())
+                Err(Error::Clipboard(
+                    "Failed to write text to clipboard".to_string(),
+                ))
             }
         })
     }
 }
 
-pub fn write_image(base64_str: String) -> Result<(), String> {
-    let img_data = STANDARD
-        .decode(base64_str)
-        .map_err(|e| format!("Failed to decode base64: {}", e))?;
-
+// 清空剪贴板, 用于过期记录
+pub fn clear() -> Result<()> {
     unsafe {
         objc::rc::autoreleasepool(|| {
             let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
             let _: () = msg_send![pasteboard, clearContents];
+        })
+    }
+    Ok(())
+}
 
-            let bytes = img_data.as_ptr() as *const std::os::raw::c_void;
-            let nsdata: id = msg_send![class!(NSData), dataWithBytes:bytes length:img_data.len()];
+// 动图以 gif 写入剪贴板才能保留动画, 其他图片按原来的 tiff 写入
+fn pasteboard_image_type(img_data: &[u8]) -> &'static str {
+    match image::guess_format(img_data) {
+        Ok(ImageFormat::Gif) => "com.compuserve.gif",
+        _ => "public.tiff",
+    }
+}
 
-            let image_type = NSString::alloc(nil).init_str("public.tiff");
-            let success: bool = msg_send![pasteboard, setData:nsdata forType:image_type];
+fn encode_img(img: &DynamicImage, format: ImageFormat) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    img.write_to(&mut Cursor::new(&mut bytes), format)?;
+    Ok(bytes)
+}
 
-            if success {
-                Ok(())
-            } else {
-                Err("Failed to write image to clipboard".to_string())
+// 原图已经是目标格式时直接使用, 否则重新编码
+fn convert_img(
+    img_data: &[u8],
+    src_format: ImageFormat,
+    img: &DynamicImage,
+    format: ImageFormat,
+) -> Result<Vec<u8>> {
+    if src_format == format {
+        return Ok(img_data.to_vec());
+    }
+    encode_img(img, format)
+}
+
+// 同时提供多种格式, 由粘贴的目标应用选择它支持的格式
+// hash 是图片 base64 的内容哈希, 和上一次粘贴的图片相同时直接使用之前的结果
+fn image_representations(
+    hash: &str,
+    img_data: &[u8],
+    paste_settings: settings::PasteSettings,
+) -> Result<Representations> {
+    let mut last_image = LAST_IMAGE.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some((last_hash, last_settings, representations)) = last_image.as_ref() {
+        if last_hash == hash && *last_settings == paste_settings {
+            return Ok(representations.clone());
+        }
+    }
+
+    let representations = Arc::new(encode_representations(img_data, &paste_settings)?);
+    *last_image = Some((hash.to_string(), paste_settings, representations.clone()));
+    Ok(representations)
+}
+
+fn encode_representations(
+    img_data: &[u8],
+    paste_settings: &settings::PasteSettings,
+) -> Result<Vec<(&'static str, Vec<u8>)>> {
+    let src_format = image::guess_format(img_data)?;
+    let img = decode_img(img_data)?;
+
+    let mut representations = Vec::new();
+    if src_format == ImageFormat::Gif {
+        representations.push(("com.compuserve.gif", img_data.to_vec()));
+    }
+    representations.push((
+        "public.tiff",
+        convert_img(img_data, src_format, &img, ImageFormat::Tiff)?,
+    ));
+    representations.push((
+        "public.png",
+        convert_img(img_data, src_format, &img, ImageFormat::Png)?,
+    ));
+    if paste_settings.jpeg {
+        let jpeg = if src_format == ImageFormat::Jpeg {
+            img_data.to_vec()
+        } else {
+            // jpeg 不支持透明通道
+            let mut bytes = Vec::new();
+            img.to_rgb8()
+                .write_with_encoder(JpegEncoder::new_with_quality(
+                    &mut bytes,
+                    paste_settings.jpeg_quality.clamp(1, 100),
+                ))?;
+            bytes
+        };
+        representations.push(("public.jpeg", jpeg));
+    }
+    Ok(representations)
+}
+
+pub fn write_image(base64_str: String) -> Result<()> {
+    let hash = db::content_hash(&base64_str);
+    let img_data = STANDARD.decode(base64_str)?;
+
+    // 无法解码的图片按原始数据写入
+    let representations = image_representations(&hash, &img_data, settings::get().paste)
+        .unwrap_or_else(|e| {
+            tracing::warn!(error = %e, "failed to convert image, writing original data");
+            Arc::new(vec![(pasteboard_image_type(&img_data), img_data.clone())])
+        });
+
+    unsafe {
+        objc::rc::autoreleasepool(|| {
+            let pasteboard: id = msg_send![class!(NSPasteboard), generalPasteboard];
+            clear_for_write(pasteboard);
+
+            for (image_type, data) in representations.iter() {
+                let bytes = data.as_ptr() as *const std::os::raw::c_void;
+                let nsdata: id = msg_send![class!(NSData), dataWithBytes:bytes length:data.len()];
+
+                let image_type = NSString::alloc(nil).init_str(image_type);
+                let success: bool = msg_send![pasteboard, setData:nsdata forType:image_type];
+                if !success {
+                    return Err(Error::Clipboard(
+                        "Failed to write image to clipboard".to_string(),
+                    ));
+                }
             }
+            Ok(())
         })
     }
 }
 
-pub fn write_file(file_path: String) -> Result<(), String> {
+// 将图片写入临时文件, 再以文件的形式写入剪贴板
+pub fn write_image_as_file(id: i64, base64_str: String) -> Result<()> {
+    let img_data = STANDARD.decode(base64_str)?;
+
+    // tiff 兼容性较差, 转换为 png, 转换失败时保留原格式
+    let (ext, bytes) = match image::guess_format(&img_data) {
+        Ok(ImageFormat::Png) => ("png", img_data),
+        Ok(ImageFormat::Jpeg) => ("jpg", img_data),
+        Ok(ImageFormat::Gif) => ("gif", img_data),
+        _ => match decode_img(&img_data).and_then(|img| encode_img(&img, ImageFormat::Png)) {
+            Ok(png) => ("png", png),
+            Err(_) => ("tiff", img_data),
+        },
+    };
+
+    let dir = std::env::temp_dir().join("clippy2");
+    let path = write_temp_image(&dir, id, ext, &bytes)?;
+
+    write_file(path.to_string_lossy().into_owned())
+}
+
+// 剪贴板同一时间只引用一个文件, 写入新的临时图片前删除之前留下的
+fn write_temp_image(dir: &Path, id: i64, ext: &str, bytes: &[u8]) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    for entry in std::fs::read_dir(dir)?.filter_map(|entry| entry.ok()) {
+        if entry.file_name().to_string_lossy().starts_with("image-") {
+            if let Err(e) = std::fs::remove_file(entry.path()) {
+                tracing::warn!(path = ?entry.path(), error = %e, "failed to remove temp image");
+            }
+        }
+    }
+
+    let path = dir.join(format!("image-{}.{}", id, ext));
+    std::fs::write(&path, bytes)?;
+    Ok(path)
+}
+
+pub fn write_file(file_path: String) -> Result<()> {
     // 检查文件是否存在
     let path = Path::new(&file_path);
     if !path.exists() {
-        return Err("File not found".to_string());
+        return Err(Error::FileNotFound);
     }
 
-    let absoulte_path = path
-        .canonicalize()
-        .map_err(|e| e.to_string())?
-        .to_string_lossy()
-        .to_string();
+    let absoulte_path = path.canonicalize()?.to_string_lossy().to_string();
 
     let script = format!(
         "set the clipboard to (POSIX file \"{}\")",
         absoulte_path.replace("\"", "\\\"")
     );
 
-    let output = Command::new("osascript")
-        .arg("-e")
-        .arg(script)
-        .output()
-        .map_err(|e| e.to_string())?;
+    let output = Command::new("osascript").arg("-e").arg(script).output()?;
 
     if !output.status.success() {
-        return Err(format!(
+        return Err(Error::Clipboard(format!(
             "Failed to write file to clipboard: {}",
             String::from_utf8_lossy(&output.stderr)
-        )
-        .into());
+        )));
     }
+    // osascript 写入时拿不到 clearContents 返回的 changeCount, 写入后再跳过
+    clipboard_read::skip_current();
 
     Ok(())
 }
@@ -92,18 +250,97 @@ pub struct RecordInput {
     pub id: i64,
     pub record_type: String,
     pub value: String,
+    // 图片以文件的形式粘贴
+    #[serde(default)]
+    pub as_file: bool,
 }
 
 #[tauri::command]
-pub async fn choose(record: RecordInput) -> Result<(), String> {
+pub async fn choose(db: State<'_, Database>, record: RecordInput) -> Result<()> {
+    db.run(move |db| write_record(db, record)).await
+}
+
+// 写入剪贴板并记为一次粘贴, 统计失败不影响粘贴
+pub fn write_record(db: &Database, record: RecordInput) -> Result<()> {
+    let id = record.id;
+    write_value(db, record)?;
+    if let Err(e) = db.record_paste(id) {
+        tracing::warn!(id, error = %e, "failed to record paste");
+    }
+    Ok(())
+}
+
+pub fn write_value(db: &Database, record: RecordInput) -> Result<()> {
     if record.record_type == "text" {
         write_text(record.value)
     } else if record.record_type == "image" {
-        let value = utils::db::get_record_value(record.id).await?;
-        write_image(value)
+        // 列表中图片的 value 为空, 从数据库读取原图
+        let value = db.get_record_value(record.id)?;
+        // 过大或来不及处理的图片只保存了占位记录
+        if value.is_empty() {
+            return Err(Error::Unsupported(
+                "Image was too large to be saved".to_string(),
+            ));
+        }
+        if record.as_file {
+            write_image_as_file(record.id, value)
+        } else {
+            write_image(value)
+        }
     } else if record.record_type == "file" {
         write_file(record.value)
     } else {
-        Err(format!("Unsupported record type: {}", record.record_type))
+        Err(Error::Unsupported(format!(
+            "Unsupported record type: {}",
+            record.record_type
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn temp_images_are_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("other.txt"), b"keep").unwrap();
+
+        let first = write_temp_image(dir.path(), 1, "png", b"first").unwrap();
+        let second = write_temp_image(dir.path(), 2, "jpg", b"second").unwrap();
+        assert!(!first.exists());
+        assert_eq!(std::fs::read(&second).unwrap(), b"second");
+        assert!(dir.path().join("other.txt").exists());
+
+        // 同一条记录再次写入时覆盖
+        let again = write_temp_image(dir.path(), 2, "jpg", b"again").unwrap();
+        assert_eq!(again, second);
+        assert_eq!(std::fs::read(&again).unwrap(), b"again");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn image_representations_are_reused() {
+        let img = DynamicImage::new_rgba8(2, 2);
+        let png = encode_img(&img, ImageFormat::Png).unwrap();
+        let paste = settings::PasteSettings::default();
+
+        let first = image_representations("a", &png, paste.clone()).unwrap();
+        let types: Vec<_> = first.iter().map(|(image_type, _)| *image_type).collect();
+        assert_eq!(types, vec!["public.tiff", "public.png"]);
+        // png 原图直接使用, 不重新编码
+        assert_eq!(first[1].1, png);
+        let again = image_representations("a", &png, paste.clone()).unwrap();
+        assert!(Arc::ptr_eq(&first, &again));
+
+        // 设置变化或换了图片时重新转换
+        let jpeg = settings::PasteSettings {
+            jpeg: true,
+            ..paste.clone()
+        };
+        let with_jpeg = image_representations("a", &png, jpeg).unwrap();
+        assert_eq!(with_jpeg.len(), 3);
+        let other = image_representations("b", &png, paste).unwrap();
+        assert!(!Arc::ptr_eq(&first, &other));
     }
 }
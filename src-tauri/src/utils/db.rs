@@ -1,43 +1,375 @@
-use lazy_static::lazy_static;
-use rusqlite::{Connection, Result};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use r2d2::Pool;
+use r2d2_sqlite::SqliteConnectionManager;
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{Connection, OpenFlags, OptionalExtension};
 use serde::{Deserialize, Serialize};
-use std::sync::Mutex;
-use tauri::Manager;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
 
-lazy_static! {
-    static ref DB: Mutex<Option<Database>> = Mutex::new(None);
-}
+use crate::utils::blob_store::BlobStore;
+use crate::utils::classify::classify;
+use crate::utils::error::{Error, Result};
+use crate::utils::maintenance;
+use crate::utils::optimize_img::hamming_distance;
+use crate::utils::settings;
+
+const DB_FILE: &str = "app.db";
+// 只读连接的数量
+const READER_COUNT: u32 = 4;
+// 数据库被锁时的等待时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[derive(Debug, Serialize)]
 pub struct Record {
-    id: i64,
-    record_type: String,
-    value: String,
-    thumbnail: Option<String>,
+    pub id: i64,
+    pub record_type: String,
+    pub value: String,
+    // 缩略图和预览图在 blob store 中的 key
+    thumbnail_key: Option<String>,
+    preview_key: Option<String>,
     // 文件size, 单位bytes
     size: Option<i64>,
     // 图片尺寸 款x高
     img_size: Option<String>,
+    // 文本的子类型(url, email, code 等)和代码语言
+    subtype: Option<String>,
+    language: Option<String>,
     created_at: String,
     updated_at: String,
     pub favorite: bool,
+    // 复制内容时激活的应用(bundle id)
+    source_app: Option<String>,
+    // 复制的次数
+    copy_count: i64,
+    // 从面板选择粘贴的次数和最后一次复制或粘贴的时间
+    paste_count: i64,
+    last_used_at: Option<String>,
+    // 过期时间(UTC), 到期后自动删除
+    expires_at: Option<String>,
+}
+
+// 与 record_from_row 的顺序一致
+const RECORD_COLUMNS: &str = "id, record_type, value, thumbnail_key, preview_key, size, img_size,
+     favorite, created_at, updated_at, subtype, language, source_app, copy_count, paste_count,
+     last_used_at, expires_at";
+
+fn record_from_row(row: &rusqlite::Row) -> Result<Record, rusqlite::Error> {
+    Ok(Record {
+        id: row.get(0)?,
+        record_type: row.get(1)?,
+        value: row.get(2)?,
+        thumbnail_key: row.get(3)?,
+        preview_key: row.get(4)?,
+        size: row.get(5)?,
+        img_size: row.get(6)?,
+        favorite: row.get::<_, i64>(7)? != 0,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        subtype: row.get(10)?,
+        language: row.get(11)?,
+        source_app: row.get(12)?,
+        copy_count: row.get(13)?,
+        paste_count: row.get(14)?,
+        last_used_at: row.get(15)?,
+        expires_at: row.get(16)?,
+    })
 }
 
+// WAL 模式下读写互不阻塞, 写操作通过单独的连接串行执行, 读操作从连接池中取连接
+#[derive(Clone)]
 pub struct Database {
-    conn: Connection,
+    writer: Arc<Mutex<Connection>>,
+    readers: Pool<SqliteConnectionManager>,
+    path: PathBuf,
+    blobs: BlobStore,
 }
 
 impl Database {
-    fn get() -> Result<std::sync::MutexGuard<'static, Option<Database>>> {
-        let db = DB.lock().unwrap();
-        if db.is_some() {
-            Ok(db)
-        } else {
-            Err(rusqlite::Error::InvalidParameterName(
-                "Database not initialized".to_string(),
-            ))
+    fn open(db_path: &Path, blobs: BlobStore) -> Result<Database> {
+        let conn = Connection::open(db_path)?;
+        conn.busy_timeout(BUSY_TIMEOUT)?;
+        conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+        migrate(&conn, &blobs)?;
+
+        let manager = SqliteConnectionManager::file(db_path)
+            .with_flags(OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+            .with_init(|conn| conn.busy_timeout(BUSY_TIMEOUT));
+        let readers = Pool::builder().max_size(READER_COUNT).build(manager)?;
+
+        Ok(Database {
+            writer: Arc::new(Mutex::new(conn)),
+            readers,
+            path: db_path.to_path_buf(),
+            blobs,
+        })
+    }
+
+    // 缩略图等二进制数据的存储, 和数据库在同一个数据目录中
+    pub fn blobs(&self) -> &BlobStore {
+        &self.blobs
+    }
+
+    // 数据库文件的路径, 备份和 VACUUM 等维护操作使用自己的连接
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn read<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T, rusqlite::Error>,
+    {
+        let conn = self.readers.get()?;
+        f(&conn).map_err(Error::from)
+    }
+
+    pub fn write<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&Connection) -> Result<T, rusqlite::Error>,
+    {
+        f(&self.writer()).map_err(Error::from)
+    }
+
+    // 持有写连接的线程 panic 后连接本身仍然可用, 忽略锁的中毒状态
+    fn writer(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.writer.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn reader(&self) -> Result<r2d2::PooledConnection<SqliteConnectionManager>> {
+        Ok(self.readers.get()?)
+    }
+
+    // 在阻塞线程池中执行数据库操作, 不占用异步运行时的线程
+    pub async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Database) -> Result<T> + Send + 'static,
+    {
+        let db = self.clone();
+        tauri::async_runtime::spawn_blocking(move || f(&db))
+            .await
+            .map_err(|e| Error::Other(e.to_string()))?
+    }
+}
+
+// 记录发生变化的 record, 同步时据此生成变更日志
+fn track_change(conn: &Connection, id: i64) -> Result<(), rusqlite::Error> {
+    conn.execute("INSERT INTO record_change (record_id) VALUES (?1)", [id])?;
+    Ok(())
+}
+
+// 修改了内容的 record, 其他设备通过修改前的哈希找到对应的记录
+fn track_edit(conn: &Connection, id: i64, old_hash: &str) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        "INSERT INTO record_change (record_id, old_hash) VALUES (?1, ?2)",
+        (id, old_hash),
+    )?;
+    Ok(())
+}
+
+// 删除符合条件的 record 之前调用, 记录被删除内容的类型和哈希, 行删除后仍然可以推送
+// 已经在回收站中的记录删除时已经记录过, 不再重复记录
+fn track_deletes(
+    conn: &Connection,
+    condition: &str,
+    params: &[&dyn rusqlite::ToSql],
+) -> Result<(), rusqlite::Error> {
+    conn.execute(
+        &format!(
+            "INSERT INTO record_change (record_id, op, record_type, old_hash)
+             SELECT id, 'delete', record_type, hash FROM record WHERE deleted_at IS NULL AND ({})",
+            condition
+        ),
+        params,
+    )?;
+    Ok(())
+}
+
+// 常用度按使用时间指数衰减, 每过一个半衰期权重减半
+const FRECENCY_HALF_LIFE_DAYS: f64 = 7.0;
+// 从面板选择粘贴比普通复制更能说明内容常用
+const COPY_WEIGHT: f64 = 1.0;
+const PASTE_WEIGHT: f64 = 2.0;
+
+// frecency 列保存 log2(Σ 权重 * 2^(使用时间 / 半衰期)), 使用时间为 unix 纪元起的天数,
+// 与按当前时间衰减后的分数只差一个所有记录共同的系数, 可以直接排序, 不需要随时间刷新
+fn frecency_point(days: f64, weight: f64) -> f64 {
+    days / FRECENCY_HALF_LIFE_DAYS + weight.log2()
+}
+
+// 在 log2 空间中累加, 避免分数溢出
+fn add_frecency(score: f64, point: f64) -> f64 {
+    let (high, low) = if score > point {
+        (score, point)
+    } else {
+        (point, score)
+    };
+    high + (low - high).exp2().ln_1p() / std::f64::consts::LN_2
+}
+
+// 记录一次使用, 更新最后使用时间和常用度
+fn track_usage(conn: &Connection, id: i64, weight: f64) -> Result<(), rusqlite::Error> {
+    let (frecency, days): (f64, f64) = conn.query_row(
+        "SELECT frecency, julianday('now') - 2440587.5 FROM record WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    conn.execute(
+        "UPDATE record SET last_used_at = strftime('%Y-%m-%d %H:%M:%f', 'now'), frecency = ?2
+         WHERE id = ?1",
+        (id, add_frecency(frecency, frecency_point(days, weight))),
+    )?;
+    Ok(())
+}
+
+// 旧数据以最近一次复制的时间和复制次数估算常用度
+fn backfill_usage(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let usages = {
+        let mut stmt = conn.prepare(
+            "SELECT id, copy_count, julianday(updated_at) - 2440587.5 FROM record
+             WHERE last_used_at IS NULL",
+        )?;
+        let usages = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, f64>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        usages
+    };
+    if usages.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (id, copy_count, days) in usages {
+        let frecency = frecency_point(days, copy_count.max(1) as f64 * COPY_WEIGHT);
+        tx.execute(
+            "UPDATE record SET last_used_at = updated_at, frecency = ?2 WHERE id = ?1",
+            (id, frecency),
+        )?;
+    }
+    tx.commit()
+}
+
+// 把旧版本以 base64 存在 thumbnail 列的缩略图写入 blob store
+fn migrate_thumbnails(conn: &Connection, blobs: &BlobStore) -> Result<(), rusqlite::Error> {
+    let thumbnails = {
+        let mut stmt = conn.prepare(
+            "SELECT id, thumbnail FROM record WHERE thumbnail IS NOT NULL AND thumbnail_key IS NULL",
+        )?;
+        let thumbnails = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        thumbnails
+    };
+
+    for (id, thumbnail) in thumbnails {
+        let key = STANDARD
+            .decode(thumbnail)
+            .map_err(Error::from)
+            .and_then(|bytes| blobs.put(&bytes, "png"));
+        if let Ok(key) = key {
+            conn.execute(
+                "UPDATE record SET thumbnail_key = ?1, thumbnail = NULL WHERE id = ?2",
+                (key, id),
+            )?;
+        }
+    }
+    Ok(())
+}
+
+// 删除 blob store 中不再被记录引用的图片
+fn gc_blobs(conn: &Connection, blobs: &BlobStore) -> Result<usize> {
+    let referenced = {
+        let mut stmt = conn.prepare(
+            "SELECT thumbnail_key FROM record WHERE thumbnail_key IS NOT NULL
+                 UNION SELECT preview_key FROM record WHERE preview_key IS NOT NULL
+                 UNION SELECT favicon_key FROM link_preview WHERE favicon_key IS NOT NULL",
+        )?;
+        let referenced = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<HashSet<_>, _>>()?;
+        referenced
+    };
+    blobs.gc(&referenced)
+}
+
+// 为没有子类型的旧文本记录分类
+fn backfill_subtypes(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let texts = {
+        let mut stmt = conn.prepare(
+            "SELECT id, value FROM record WHERE record_type = 'text' AND subtype IS NULL",
+        )?;
+        let texts = stmt
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        texts
+    };
+
+    let tx = conn.unchecked_transaction()?;
+    for (id, value) in texts {
+        let classification = classify(&value);
+        tx.execute(
+            "UPDATE record SET subtype = ?1, language = ?2 WHERE id = ?3",
+            (classification.subtype, classification.language, id),
+        )?;
+    }
+    tx.commit()
+}
+
+// 为没有哈希的旧记录计算哈希, 并合并内容重复的记录, 保证唯一索引可以创建
+fn backfill_hashes(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let hashes = {
+        let mut stmt = conn.prepare("SELECT id, value FROM record WHERE hash IS NULL")?;
+        let mut rows = stmt.query([])?;
+        let mut hashes = Vec::new();
+        while let Some(row) = rows.next()? {
+            let id: i64 = row.get(0)?;
+            let value: String = row.get(1)?;
+            hashes.push((id, content_hash(&value)));
         }
+        hashes
+    };
+    if hashes.is_empty() {
+        return Ok(());
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    for (id, hash) in &hashes {
+        tx.execute("UPDATE record SET hash = ?1 WHERE id = ?2", (hash, id))?;
     }
+    // 重复的记录中保留最近使用的一条, 收藏状态合并到保留的记录上
+    tx.execute(
+        "UPDATE record SET favorite = 1
+         WHERE favorite = 0 AND EXISTS (
+             SELECT 1 FROM record r
+             WHERE r.record_type = record.record_type AND r.hash = record.hash AND r.favorite = 1
+         )",
+        [],
+    )?;
+    tx.execute(
+        "DELETE FROM record WHERE EXISTS (
+             SELECT 1 FROM record r
+             WHERE r.record_type = record.record_type AND r.hash = record.hash
+               AND (r.updated_at > record.updated_at
+                    OR (r.updated_at = record.updated_at AND r.id > record.id))
+         )",
+        [],
+    )?;
+    tx.commit()
 }
 
 fn create_indexes(conn: &Connection) -> Result<(), rusqlite::Error> {
@@ -46,23 +378,82 @@ fn create_indexes(conn: &Connection) -> Result<(), rusqlite::Error> {
         "CREATE INDEX IF NOT EXISTS idx_record_type ON record(record_type)",
         [],
     )?;
+    // 列表按 (updated_at, id) 排序和分页
+    conn.execute("DROP INDEX IF EXISTS idx_updated_at", [])?;
     conn.execute(
-        "CREATE INDEX IF NOT EXISTS idx_updated_at ON record(updated_at)",
+        "CREATE INDEX IF NOT EXISTS idx_updated_at_id ON record(updated_at, id)",
         [],
     )?;
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_favorite ON record(favorite)",
         [],
     )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_copy_count_id ON record(copy_count, id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_frecency_id ON record(frecency, id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_source_app ON record(source_app)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_expires_at ON record(expires_at)",
+        [],
+    )?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_phash ON record(phash)", [])?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_subtype ON record(subtype)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE UNIQUE INDEX IF NOT EXISTS idx_record_hash ON record(record_type, hash)",
+        [],
+    )?;
     Ok(())
 }
 
-pub fn init(app: &tauri::App) {
-    let app_dir = app.app_handle().path().app_data_dir().unwrap();
-    std::fs::create_dir_all(&app_dir).unwrap();
-    let db_path = app_dir.join("app.db");
+// 命令通过 tauri 的 State 获取数据库, 后台线程在初始化时传入
+pub fn init(app: &tauri::App) -> Result<Database> {
+    let app_dir = app.app_handle().path().app_data_dir()?;
+    let db = init_at(&app_dir)?;
+    app.manage(db.clone());
+    app.manage(db.blobs().clone());
+    maintenance::init(db.clone());
+
+    // 定时清理已过撤销时间的软删除记录
+    let purge_db = db.clone();
+    std::thread::spawn(move || loop {
+        if let Err(e) = purge_db.purge_deleted() {
+            tracing::error!(error = %e, "failed to purge deleted records");
+        }
+        std::thread::sleep(Duration::from_secs(UNDO_WINDOW_SECS as u64));
+    });
+    Ok(db)
+}
+
+// 命令行下没有 tauri::App, 直接指定数据目录
+pub fn init_at(app_dir: &Path) -> Result<Database> {
+    std::fs::create_dir_all(app_dir)?;
+    let blobs = BlobStore::open(app_dir)?;
+
+    let db_path = app_dir.join(DB_FILE);
+    maintenance::check_and_recover(&db_path)?;
+    Database::open(&db_path, blobs)
+}
+
+// 不检查和恢复数据库, 用于命令行: 界面可能正在使用数据库, 不能改名或替换数据库文件
+pub fn open_at(app_dir: &Path) -> Result<Database> {
+    std::fs::create_dir_all(app_dir)?;
+    let blobs = BlobStore::open(app_dir)?;
+    Database::open(&app_dir.join(DB_FILE), blobs)
+}
 
-    let conn = Connection::open(db_path).unwrap();
+// 建表和升级旧版本的数据
+fn migrate(conn: &Connection, blobs: &BlobStore) -> Result<()> {
     conn.execute(
         "CREATE TABLE IF NOT EXISTS record (
             id INTEGER PRIMARY KEY AUTOINCREMENT,
@@ -72,12 +463,11 @@ pub fn init(app: &tauri::App) {
             size INTEGER,
             img_size TEXT,
             favorite INTEGER DEFAULT 0,
-            created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
-            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            created_at DATETIME DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now')),
+            updated_at DATETIME DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
         )",
         [],
-    )
-    .unwrap();
+    )?;
 
     // 确保 favorite 列存在
     conn.execute(
@@ -86,202 +476,1959 @@ pub fn init(app: &tauri::App) {
     )
     .unwrap_or_else(|_| 0);
 
-    // 创建索引
-    create_indexes(&conn).unwrap();
+    // 软删除时间, 超过撤销时间后才真正删除
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN deleted_at DATETIME", []);
+
+    // 图片中识别出的文字, NULL 表示尚未识别
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN ocr_text TEXT", []);
+
+    // 图片的感知哈希, 用于合并相似图片
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN phash INTEGER", []);
+
+    // 文本的子类型, 旧数据在这里补齐
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN subtype TEXT", []);
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN language TEXT", []);
+    backfill_subtypes(conn)?;
+
+    // 代码高亮后的 HTML 缓存, NULL 表示尚未生成
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN highlight_html TEXT", []);
+
+    // 待同步的变更, 推送到同步目录后删除
+    // 删除的变更保留被删除内容的类型和哈希, 修改的变更保留修改前的哈希
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS record_change (
+            seq INTEGER PRIMARY KEY AUTOINCREMENT,
+            record_id INTEGER NOT NULL,
+            op TEXT NOT NULL DEFAULT 'upsert',
+            record_type TEXT,
+            old_hash TEXT,
+            changed_at DATETIME NOT NULL DEFAULT (strftime('%Y-%m-%d %H:%M:%f', 'now'))
+        )",
+        [],
+    )?;
+
+    // 编辑文本记录前的原始内容
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS record_revision (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            record_id INTEGER NOT NULL,
+            value TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // 各设备变更日志的同步进度(已合并的行数和最后合并的一行), 用于发现被重建的日志
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS sync_cursor (
+            device_id TEXT PRIMARY KEY,
+            position INTEGER NOT NULL DEFAULT 0,
+            last_line TEXT
+        )",
+        [],
+    )?;
+
+    // 链接的标题和图标, 以复制的文本为 key 缓存, 抓取失败时 title 为 NULL, 不再重试
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS link_preview (
+            url TEXT PRIMARY KEY,
+            title TEXT,
+            description TEXT,
+            favicon_key TEXT,
+            fetched_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // 缩略图改为存放在 blob store 中, 旧的 base64 缩略图在这里迁移
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN thumbnail_key TEXT", []);
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN preview_key TEXT", []);
+    migrate_thumbnails(conn, blobs)?;
+
+    // 内容哈希, 旧数据在这里补齐
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN hash TEXT", []);
+    backfill_hashes(conn)?;
+
+    // 时间戳改为毫秒精度, 旧数据补齐小数部分, 保证字符串比较的顺序正确
+    conn.execute(
+        "UPDATE record SET created_at = strftime('%Y-%m-%d %H:%M:%f', created_at)
+         WHERE length(created_at) = 19",
+        [],
+    )?;
+    conn.execute(
+        "UPDATE record SET updated_at = strftime('%Y-%m-%d %H:%M:%f', updated_at)
+         WHERE length(updated_at) = 19",
+        [],
+    )?;
+
+    // 复制内容时激活的应用和复制次数
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN source_app TEXT", []);
+    let _ = conn.execute(
+        "ALTER TABLE record ADD COLUMN copy_count INTEGER NOT NULL DEFAULT 1",
+        [],
+    );
+
+    // 使用统计: 从面板粘贴的次数、最后使用时间和常用度
+    let _ = conn.execute(
+        "ALTER TABLE record ADD COLUMN paste_count INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN last_used_at DATETIME", []);
+    let _ = conn.execute(
+        "ALTER TABLE record ADD COLUMN frecency REAL NOT NULL DEFAULT 0",
+        [],
+    );
+    backfill_usage(conn)?;
+
+    // 临时记录的过期时间, NULL 表示永不过期
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN expires_at DATETIME", []);
+
+    // 最后一次修改收藏的时间, 同步时按它合并各设备的收藏状态
+    let _ = conn.execute("ALTER TABLE record ADD COLUMN favorite_at DATETIME", []);
 
-    let mut db = DB.lock().unwrap();
-    *db = Some(Database { conn });
+    // 创建索引
+    create_indexes(conn)?;
+    Ok(())
 }
 
 #[derive(Debug, Deserialize)]
 pub struct RecordInput {
     pub record_type: String,
     pub value: String,
-    pub thumbnail: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub preview_key: Option<String>,
     pub size: Option<u64>,
     pub img_size: Option<String>,
+    // 图片的感知哈希
+    pub phash: Option<i64>,
+    // 复制内容时激活的应用(bundle id)
+    #[serde(default)]
+    pub source_app: Option<String>,
+}
+
+// 测试中构造记录, 默认是没有附加信息的文本
+#[cfg(test)]
+impl RecordInput {
+    pub fn text(value: &str) -> RecordInput {
+        RecordInput {
+            record_type: "text".to_string(),
+            value: value.to_string(),
+            thumbnail_key: None,
+            preview_key: None,
+            size: None,
+            img_size: None,
+            phash: None,
+            source_app: None,
+        }
+    }
+
+    pub fn image(value: &str, phash: i64, img_size: &str) -> RecordInput {
+        RecordInput {
+            record_type: "image".to_string(),
+            img_size: Some(img_size.to_string()),
+            phash: Some(phash),
+            ..RecordInput::text(value)
+        }
+    }
+
+    pub fn with_type(self, record_type: &str) -> RecordInput {
+        RecordInput {
+            record_type: record_type.to_string(),
+            ..self
+        }
+    }
 }
 
-// 通过 record_type 和 value 检查是否存在相同的记录
+// 记录内容的 SHA-256, 用于去重, 避免直接比较可能有几 MB 的 value
+pub fn content_hash(value: &str) -> String {
+    Sha256::digest(value.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+// 通过 record_type 和内容哈希检查是否存在相同的记录
 pub fn check_record_exists(
     conn: &Connection,
     record_type: &str,
-    value: &str,
+    hash: &str,
+) -> Result<Option<i64>, rusqlite::Error> {
+    let mut stmt = conn.prepare("SELECT id FROM record WHERE record_type = ?1 AND hash = ?2")?;
+
+    stmt.query_row([record_type, hash], |row| row.get(0))
+        .optional()
+}
+
+// 相近哈希只和最近的这些图片比较, 避免每次复制图片都扫描全部记录
+const SIMILAR_SCAN_LIMIT: i64 = 500;
+
+// 解析 `宽x高` 格式的尺寸
+fn parse_img_size(img_size: &str) -> Option<(u64, u64)> {
+    let (width, height) = img_size.split_once('x')?;
+    let (width, height) = (width.parse().ok()?, height.parse().ok()?);
+    (width > 0 && height > 0).then_some((width, height))
+}
+
+// 缩放后的图片宽高比相同, 允许 1% 的取整误差
+fn same_aspect_ratio(a: (u64, u64), b: (u64, u64)) -> bool {
+    let (left, right) = (a.0 * b.1, b.0 * a.1);
+    left.abs_diff(right) * 100 <= left.max(right)
+}
+
+// 查找感知哈希相近且宽高比相同的图片记录, 返回最近使用的一条
+fn find_similar_image(
+    conn: &Connection,
+    phash: u64,
+    img_size: Option<&str>,
+    max_distance: u32,
 ) -> Result<Option<i64>, rusqlite::Error> {
+    // 裁剪过的图片哈希也可能很接近, 尺寸未知时不合并
+    let Some(size) = img_size.and_then(parse_img_size) else {
+        return Ok(None);
+    };
+    let matches = |other_phash: i64, other_size: Option<String>| {
+        hamming_distance(phash, other_phash as u64) <= max_distance
+            && other_size
+                .as_deref()
+                .and_then(parse_img_size)
+                .is_some_and(|other| same_aspect_ratio(size, other))
+    };
+
     let mut stmt = conn.prepare(
-        "SELECT id FROM record 
-         WHERE record_type = ?1 AND value = ?2 
-         ORDER BY updated_at DESC LIMIT 1",
+        "SELECT id, phash, img_size FROM record
+         WHERE record_type = 'image' AND phash = ?1
+         ORDER BY updated_at DESC",
     )?;
+    let mut rows = stmt.query([phash as i64])?;
+    while let Some(row) = rows.next()? {
+        if matches(row.get(1)?, row.get(2)?) {
+            return Ok(Some(row.get(0)?));
+        }
+    }
+    if max_distance == 0 {
+        return Ok(None);
+    }
 
-    Ok(stmt.query_row([record_type, value], |row| row.get(0)).ok())
+    // SQLite 没有 popcount, 取出最近的哈希在内存中比较
+    let mut stmt = conn.prepare(
+        "SELECT id, phash, img_size FROM record
+         WHERE record_type = 'image' AND phash IS NOT NULL
+         ORDER BY updated_at DESC LIMIT ?1",
+    )?;
+    let mut rows = stmt.query([SIMILAR_SCAN_LIMIT])?;
+    while let Some(row) = rows.next()? {
+        if matches(row.get(1)?, row.get(2)?) {
+            return Ok(Some(row.get(0)?));
+        }
+    }
+    Ok(None)
 }
 
-#[tauri::command]
-pub async fn add_record(record: RecordInput) -> Result<i64, String> {
-    let db = Database::get().map_err(|e| e.to_string())?;
-    let db = db.as_ref().unwrap();
-
-    let existing_id = check_record_exists(&db.conn, &record.record_type, &record.value)
-        .map_err(|e| e.to_string())?;
-
-    if let Some(id) = existing_id {
-        // 如果存在，更新时间戳
-        db.conn
-            .execute(
-                "UPDATE record SET updated_at = CURRENT_TIMESTAMP WHERE id = ?1",
-                [id],
-            )
-            .map_err(|e| e.to_string())?;
-        return Ok(id);
-    }
-    // 如果不存在，插入新记录
-    db.conn
-        .execute(
-            "INSERT INTO record (record_type, value, thumbnail, size, img_size, favorite, updated_at) 
-                 VALUES (?1, ?2, ?3, ?4, ?5, 0, CURRENT_TIMESTAMP)",
-            (
-                record.record_type,
-                record.value,
-                record.thumbnail,
-                record.size,
-                record.img_size,
-            ),
-        )
-        .map_err(|e| e.to_string())?;
+// 记录内容的字节数, 图片按 base64 解码后的大小计算(去掉末尾的填充),
+// 只保存了大小的图片(占位记录)使用记录的 size
+pub(crate) const SIZE_EXPR: &str = "(CASE
+     WHEN record_type = 'file' THEN COALESCE(size, 0)
+     WHEN record_type = 'image' AND value = '' THEN COALESCE(size, 0)
+     WHEN record_type = 'image' THEN length(value) * 3 / 4
+         - (substr(value, -1) = '=') - (substr(value, -2) = '==')
+     ELSE length(CAST(value AS BLOB))
+ END)";
+// img_size 的格式为 宽x高
+const WIDTH_EXPR: &str = "CAST(substr(img_size, 1, instr(img_size, 'x') - 1) AS INTEGER)";
+const HEIGHT_EXPR: &str = "CAST(substr(img_size, instr(img_size, 'x') + 1) AS INTEGER)";
 
-    Ok(db.conn.last_insert_rowid())
+// 排序方式, 都是从大到小排列, 相同时按 id 排列
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    // 最近使用
+    #[default]
+    Recent,
+    // 复制次数
+    Frequency,
+    // 内容大小
+    Size,
+    // 常用度, 综合使用次数和最近使用时间
+    Frecency,
 }
 
-#[derive(Debug, Deserialize)]
+impl SortBy {
+    fn key_expr(&self) -> &'static str {
+        match self {
+            SortBy::Recent => "updated_at",
+            SortBy::Frequency => "copy_count",
+            SortBy::Size => SIZE_EXPR,
+            SortBy::Frecency => "frecency",
+        }
+    }
+}
+
+// 翻页方向, 列表按排序字段从大到小排列
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageDirection {
+    // 游标之后(更早/更小)的记录
+    #[default]
+    Forward,
+    // 游标之前(更新/更大)的记录
+    Backward,
+}
+
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+
+fn default_page_size() -> u32 {
+    DEFAULT_PAGE_SIZE
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 pub struct QueryParams {
-    pub last_updated_at: Option<String>,
+    // 上一页返回的游标, 不传时从第一条记录开始
+    pub cursor: Option<String>,
+    pub direction: PageDirection,
+    pub sort: SortBy,
+    // 每页的数量, 不传时为 DEFAULT_PAGE_SIZE, 按条件删除时不需要
+    #[serde(default = "default_page_size")]
     pub limit: u32,
     pub keyword: Option<String>,
     pub record_type: Option<String>,
+    // 同时匹配多种类型, 与 record_type 合并
+    pub record_types: Vec<String>,
     pub favorite: Option<bool>,
+    // 文本子类型, 见 classify
+    pub subtype: Option<String>,
+    // 代码语言, 只对 code 子类型有效
+    pub language: Option<String>,
+    // 时间范围(UTC, 包含边界), 格式为 YYYY-MM-DD HH:MM:SS, 只有日期时为当天 0 点
+    pub created_after: Option<String>,
+    pub created_before: Option<String>,
+    pub updated_after: Option<String>,
+    pub updated_before: Option<String>,
+    // 内容大小范围, 单位 bytes
+    pub min_size: Option<i64>,
+    pub max_size: Option<i64>,
+    // 图片尺寸范围, 单位像素, 只匹配图片记录
+    pub min_width: Option<i64>,
+    pub max_width: Option<i64>,
+    pub min_height: Option<i64>,
+    pub max_height: Option<i64>,
+    // 文件扩展名, 如 ["pdf", "png"], 只匹配文件记录
+    pub extensions: Vec<String>,
+    // 复制时激活的应用(bundle id)
+    pub source_apps: Vec<String>,
 }
 
-#[tauri::command]
-pub async fn get_records(params: QueryParams) -> Result<Vec<Record>, String> {
-    let db = Database::get().map_err(|e| e.to_string())?;
-    let db = db.as_ref().unwrap();
-
-    // 当type=image时, value是图片base64, 数据太大了, 置为空字符串(渲染时用thumbnail够了)
-    let base_query = "SELECT id, record_type, 
-             CASE 
-                WHEN record_type = 'image' THEN ''
-                ELSE value 
-             END as value,
-             thumbnail, size, img_size, favorite, created_at, updated_at 
-             FROM record";
-
-    let mut conditions = Vec::new();
+#[derive(Debug, Serialize)]
+pub struct RecordPage {
+    pub records: Vec<Record>,
+    // 继续加载后面记录的游标, 没有更多时为 None
+    pub next_cursor: Option<String>,
+    // 加载前面记录的游标, 有新复制的内容时可以用它向前翻页
+    pub prev_cursor: Option<String>,
+}
+
+// 游标对调用方不透明, 内容是排序方式和记录的 (排序字段, id)
+// 常用度是浮点数, 依赖 serde_json 的 float_roundtrip 特性精确还原, 否则翻页时会重复或跳过记录
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: SortBy,
+    key: serde_json::Value,
+    id: i64,
+}
+
+fn encode_cursor(sort: SortBy, key: &SqlValue, id: i64) -> String {
+    let key = match key {
+        SqlValue::Integer(n) => serde_json::Value::from(*n),
+        SqlValue::Real(n) => serde_json::Value::from(*n),
+        SqlValue::Text(text) => serde_json::Value::from(text.as_str()),
+        SqlValue::Null | SqlValue::Blob(_) => serde_json::Value::Null,
+    };
+    let cursor = serde_json::to_vec(&Cursor { sort, key, id }).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(cursor)
+}
+
+fn decode_cursor(cursor: &str, sort: SortBy) -> Result<(SqlValue, i64)> {
+    let invalid = || Error::InvalidInput("Invalid cursor".to_string());
+    let bytes = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let cursor: Cursor = serde_json::from_slice(&bytes).map_err(|_| invalid())?;
+    // 游标只能在生成它的排序方式下使用
+    if cursor.sort != sort {
+        return Err(invalid());
+    }
+    let key = match cursor.key {
+        serde_json::Value::String(text) => SqlValue::Text(text),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(n) => SqlValue::Integer(n),
+            None => SqlValue::Real(n.as_f64().ok_or_else(invalid)?),
+        },
+        _ => return Err(invalid()),
+    };
+    Ok((key, cursor.id))
+}
+
+// 生成 IN (?, ?, ...) 的占位符
+fn placeholders(count: usize) -> String {
+    vec!["?"; count].join(", ")
+}
+
+// 转义 LIKE 的通配符, 配合 ESCAPE 使用
+fn escape_like(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// 固定位数的数字
+fn parse_digits(value: &str, len: usize) -> Option<u32> {
+    (value.len() == len && value.bytes().all(|b| b.is_ascii_digit()))
+        .then(|| value.parse().ok())
+        .flatten()
+}
+
+// 检查 YYYY-MM-DD[ HH:MM[:SS[.fff]]] 格式, 日期和时间之间也可以是 T
+fn is_valid_datetime(value: &str) -> bool {
+    let (date, time) = match value.split_once([' ', 'T']) {
+        Some((date, time)) => (date, Some(time)),
+        None => (value, None),
+    };
+
+    let mut parts = date.split('-');
+    let (Some(year), Some(month), Some(day), None) = (
+        parts.next().and_then(|p| parse_digits(p, 4)),
+        parts.next().and_then(|p| parse_digits(p, 2)),
+        parts.next().and_then(|p| parse_digits(p, 2)),
+        parts.next(),
+    ) else {
+        return false;
+    };
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    let days = match month {
+        1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+        4 | 6 | 9 | 11 => 30,
+        2 if leap => 29,
+        2 => 28,
+        _ => return false,
+    };
+    if day == 0 || day > days {
+        return false;
+    }
+
+    let Some(time) = time else {
+        return true;
+    };
+    let mut parts = time.split(':');
+    let (Some(hour), Some(minute), second, None) =
+        (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+        return false;
+    };
+    let second_valid = match second.map(|s| s.split_once('.').unwrap_or((s, "0"))) {
+        None => true,
+        Some((second, fraction)) => {
+            parse_digits(second, 2).is_some_and(|s| s < 60)
+                && !fraction.is_empty()
+                && fraction.bytes().all(|b| b.is_ascii_digit())
+        }
+    };
+    parse_digits(hour, 2).is_some_and(|h| h < 24)
+        && parse_digits(minute, 2).is_some_and(|m| m < 60)
+        && second_valid
+}
+
+// WHERE 条件和对应的参数, 条件中只有占位符, 值都通过参数传入
+type Conditions = (Vec<String>, Vec<Box<dyn rusqlite::ToSql>>);
+
+// 根据查询参数生成 WHERE 条件, 已删除(等待清理)和已过期的记录始终排除
+fn build_conditions(params: QueryParams) -> Result<Conditions> {
+    let mut conditions = vec![
+        "deleted_at IS NULL".to_string(),
+        "(expires_at IS NULL OR expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now'))".to_string(),
+    ];
     let mut query_params: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
 
     if let Some(keyword) = params.keyword {
         if !keyword.is_empty() {
-            conditions.push("record_type IN ('text', 'file') AND value LIKE ?");
-            query_params.push(Box::new(format!("%{}%", keyword)));
+            // 图片按识别出的文字搜索, 与文本一样按子串匹配
+            // 不使用 FTS5: 默认分词器不切分中文, 搜不到词中间的字; trigram 分词器至少要 3 个字符,
+            // 且系统自带的 SQLite 版本不一定支持. 识别结果一般只有几百字, 与 value 一起扫描的开销可以接受
+            // 关键字中的 % 和 _ 按字面匹配
+            conditions.push(
+                "((record_type IN ('text', 'file') AND value LIKE ? ESCAPE '\\')
+                  OR (record_type = 'image' AND ocr_text LIKE ? ESCAPE '\\'))"
+                    .to_string(),
+            );
+            let pattern = format!("%{}%", escape_like(&keyword));
+            query_params.push(Box::new(pattern.clone()));
+            query_params.push(Box::new(pattern));
         }
     }
 
+    let mut record_types = params.record_types;
     if let Some(record_type) = params.record_type {
         if record_type != "all" {
-            conditions.push("record_type = ?");
+            record_types.push(record_type);
+        }
+    }
+    if !record_types.is_empty() {
+        conditions.push(format!(
+            "record_type IN ({})",
+            placeholders(record_types.len())
+        ));
+        for record_type in record_types {
             query_params.push(Box::new(record_type));
         }
     }
 
-    if let Some(last_updated_at) = params.last_updated_at {
-        conditions.push("updated_at < ?");
-        query_params.push(Box::new(last_updated_at));
+    if let Some(cursor) = params.cursor {
+        let (key, id) = decode_cursor(&cursor, params.sort)?;
+        let op = match params.direction {
+            PageDirection::Forward => "<",
+            PageDirection::Backward => ">",
+        };
+        conditions.push(format!("({}, id) {} (?, ?)", params.sort.key_expr(), op));
+        query_params.push(Box::new(key));
+        query_params.push(Box::new(id));
     }
 
     if let Some(favorite) = params.favorite {
         if favorite {
-            conditions.push("favorite = 1");
+            conditions.push("favorite = 1".to_string());
         }
     }
 
-    let query = if conditions.is_empty() {
-        format!("{} ORDER BY updated_at DESC LIMIT ?", base_query)
-    } else {
-        format!(
-            "{} WHERE {} ORDER BY updated_at DESC LIMIT ?",
-            base_query,
-            conditions.join(" AND ")
-        )
-    };
-
-    query_params.push(Box::new(params.limit));
-
-    let mut stmt = db.conn.prepare(&query).map_err(|e| e.to_string())?;
-    let params_slice: Vec<&dyn rusqlite::ToSql> = query_params.iter().map(|p| p.as_ref()).collect();
-
-    let records = stmt
-        .query_map(params_slice.as_slice(), |row| {
-            Ok(Record {
-                id: row.get(0)?,
-                record_type: row.get(1)?,
-                value: row.get(2)?,
-                thumbnail: row.get(3)?,
-                size: row.get(4)?,
-                img_size: row.get(5)?,
-                favorite: row.get::<_, i64>(6)? != 0,
-                created_at: row.get(7)?,
-                updated_at: row.get(8)?,
-            })
-        })
-        .map_err(|e| e.to_string())?;
+    if let Some(subtype) = params.subtype {
+        conditions.push("subtype = ?".to_string());
+        query_params.push(Box::new(subtype));
+    }
 
-    records
-        .collect::<Result<Vec<_>, _>>()
-        .map_err(|e| e.to_string())
-}
+    if let Some(language) = params.language {
+        conditions.push("language = ?".to_string());
+        query_params.push(Box::new(language));
+    }
 
-#[tauri::command]
-pub async fn get_record_value(id: i64) -> Result<String, String> {
-    let db = Database::get().map_err(|e| e.to_string())?;
-    let db = db.as_ref().unwrap();
+    // 统一为毫秒精度的格式再比较
+    let date_ranges = [
+        ("created_at", ">=", params.created_after),
+        ("created_at", "<=", params.created_before),
+        ("updated_at", ">=", params.updated_after),
+        ("updated_at", "<=", params.updated_before),
+    ];
+    for (column, op, value) in date_ranges {
+        if let Some(value) = value {
+            // strftime 遇到无法解析的时间返回 NULL, 条件不会匹配任何记录, 需要先检查
+            if !is_valid_datetime(&value) {
+                return Err(Error::InvalidInput(format!("Invalid date: {}", value)));
+            }
+            conditions.push(format!(
+                "{} {} strftime('%Y-%m-%d %H:%M:%f', ?)",
+                column, op
+            ));
+            query_params.push(Box::new(value));
+        }
+    }
 
-    let mut stmt = db
-        .conn
-        .prepare("SELECT value FROM record WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+    let size_ranges = [
+        (SIZE_EXPR, ">=", params.min_size),
+        (SIZE_EXPR, "<=", params.max_size),
+    ];
+    // 图片尺寸条件只匹配有尺寸的图片
+    let dimension_ranges = [
+        (WIDTH_EXPR, ">=", params.min_width),
+        (WIDTH_EXPR, "<=", params.max_width),
+        (HEIGHT_EXPR, ">=", params.min_height),
+        (HEIGHT_EXPR, "<=", params.max_height),
+    ];
+    if dimension_ranges.iter().any(|(_, _, value)| value.is_some()) {
+        conditions.push("(record_type = 'image' AND img_size IS NOT NULL)".to_string());
+    }
+    for (expr, op, value) in size_ranges.into_iter().chain(dimension_ranges) {
+        if let Some(value) = value {
+            conditions.push(format!("{} {} ?", expr, op));
+            query_params.push(Box::new(value));
+        }
+    }
+
+    let extensions: Vec<String> = params
+        .extensions
+        .iter()
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect();
+    if !extensions.is_empty() {
+        let patterns = vec!["lower(value) LIKE ? ESCAPE '\\'"; extensions.len()].join(" OR ");
+        conditions.push(format!("(record_type = 'file' AND ({}))", patterns));
+        for ext in extensions {
+            query_params.push(Box::new(format!("%.{}", escape_like(&ext))));
+        }
+    }
+
+    if !params.source_apps.is_empty() {
+        conditions.push(format!(
+            "source_app IN ({})",
+            placeholders(params.source_apps.len())
+        ));
+        for source_app in params.source_apps {
+            query_params.push(Box::new(source_app));
+        }
+    }
 
-    stmt.query_row([id], |row| Ok(row.get(0)?))
-        .map_err(|e| e.to_string())
+    Ok((conditions, query_params))
 }
 
-#[tauri::command]
-pub async fn toggle_favorite(id: i64) -> Result<(), String> {
-    let db = Database::get().map_err(|e| e.to_string())?;
-    let db = db.as_ref().unwrap();
+impl Database {
+    pub fn add_record(&self, record: RecordInput) -> Result<i64> {
+        self.insert_record(record, true)
+    }
+
+    // 只按内容去重, 不合并相似的图片, 用于编辑后的图片(裁剪、打码后与原图的感知哈希很接近)
+    pub fn add_record_exact(&self, record: RecordInput) -> Result<i64> {
+        self.insert_record(record, false)
+    }
+
+    fn insert_record(&self, record: RecordInput, merge_similar: bool) -> Result<i64> {
+        let conn = self.writer();
+
+        let hash = content_hash(&record.value);
+        let classification = (record.record_type == "text").then(|| classify(&record.value));
+
+        // 内容不同但看起来是同一张图(缩放/格式不同), 合并到已有记录
+        if merge_similar && record.record_type == "image" {
+            let dedup = settings::get().image_dedup;
+            let exists = check_record_exists(&conn, &record.record_type, &hash)?;
+            if let (None, true, Some(phash)) = (exists, dedup.enabled, record.phash) {
+                let similar_id = find_similar_image(
+                    &conn,
+                    phash as u64,
+                    record.img_size.as_deref(),
+                    dedup.max_distance,
+                )?;
+                if let Some(id) = similar_id {
+                    conn.execute(
+                        "UPDATE record SET updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now'),
+                             expires_at = CASE WHEN deleted_at IS NULL
+                                 AND expires_at > strftime('%Y-%m-%d %H:%M:%f', 'now') THEN expires_at END,
+                             deleted_at = NULL,
+                             copy_count = copy_count + 1, source_app = COALESCE(?2, source_app)
+                         WHERE id = ?1",
+                        (id, &record.source_app),
+                    )?;
+                    track_usage(&conn, id, COPY_WEIGHT)?;
+                    track_change(&conn, id)?;
+                    return Ok(id);
+                }
+            }
+        }
+
+        // 插入新记录, 如果已存在相同内容，只更新时间戳、复制次数和来源
+        // 已删除或已过期的临时记录重新复制后不再过期, 仍在有效期内的保留原来的过期时间
+        conn.execute(
+            "INSERT INTO record (record_type, value, hash, thumbnail_key, preview_key, size, img_size, phash, subtype, language, source_app, favorite, created_at, updated_at) 
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, 0,
+                         strftime('%Y-%m-%d %H:%M:%f', 'now'), strftime('%Y-%m-%d %H:%M:%f', 'now'))
+             ON CONFLICT(record_type, hash) DO UPDATE SET
+                 updated_at = excluded.updated_at,
+                 expires_at = CASE WHEN deleted_at IS NULL
+                     AND expires_at > excluded.updated_at THEN expires_at END,
+                 deleted_at = NULL,
+                 copy_count = copy_count + 1,
+                 source_app = COALESCE(excluded.source_app, source_app)",
+            (
+                &record.record_type,
+                record.value,
+                &hash,
+                record.thumbnail_key,
+                record.preview_key,
+                record.size,
+                record.img_size,
+                record.phash,
+                classification.map(|c| c.subtype),
+                classification.and_then(|c| c.language),
+                record.source_app,
+            ),
+        )?;
+
+        // 冲突更新时 last_insert_rowid 不会变化, 通过索引查回 id
+        let id = check_record_exists(&conn, &record.record_type, &hash)?
+            .ok_or_else(|| Error::Other("Failed to add record".to_string()))?;
+        track_usage(&conn, id, COPY_WEIGHT)?;
+        track_change(&conn, id)?;
+
+        Ok(id)
+    }
+
+    pub fn get_records(&self, params: QueryParams) -> Result<RecordPage> {
+        if params.limit == 0 {
+            return Err(Error::InvalidInput("Limit must be positive".to_string()));
+        }
+        let conn = self.reader()?;
+
+        let limit = params.limit as usize;
+        let direction = params.direction;
+        let sort = params.sort;
+        let has_cursor = params.cursor.is_some();
+        let (conditions, mut query_params) = build_conditions(params)?;
+
+        // 当type=image时, value是图片base64, 数据太大了, 置为空字符串(渲染时用缩略图够了)
+        // 排序字段放在最后一列, 用于生成游标
+        // 向后翻页时取紧挨着游标的记录, 查询后再反转为从大到小
+        let order = match direction {
+            PageDirection::Forward => "DESC",
+            PageDirection::Backward => "ASC",
+        };
+        let query = format!(
+            "SELECT id, record_type,
+                 CASE WHEN record_type = 'image' THEN '' ELSE value END AS value,
+                 thumbnail_key, preview_key, size, img_size, favorite, created_at, updated_at,
+                 subtype, language, source_app, copy_count, paste_count, last_used_at,
+                 expires_at, {key} AS sort_key
+             FROM record
+             WHERE {conditions}
+             ORDER BY {key} {order}, id {order} LIMIT ?",
+            key = sort.key_expr(),
+            conditions = conditions.join(" AND "),
+        );
+
+        // 多取一条用于判断是否还有更多
+        query_params.push(Box::new(limit as i64 + 1));
+
+        let mut stmt = conn.prepare(&query)?;
+        let params_slice: Vec<&dyn rusqlite::ToSql> =
+            query_params.iter().map(|p| p.as_ref()).collect();
+
+        let rows = stmt.query_map(params_slice.as_slice(), |row| {
+            Ok((record_from_row(row)?, row.get::<_, SqlValue>(17)?))
+        })?;
+
+        let mut rows = rows.collect::<rusqlite::Result<Vec<_>>>()?;
+        let has_more = rows.len() > limit;
+        rows.truncate(limit);
+        if direction == PageDirection::Backward {
+            rows.reverse();
+        }
 
-    db.conn
-        .execute(
-            "UPDATE record SET favorite = NOT favorite WHERE id = ?1",
+        let cursor_of = |row: Option<&(Record, SqlValue)>| {
+            row.map(|(record, key)| encode_cursor(sort, key, record.id))
+        };
+        // 向后翻页时游标本身就是排在后面的记录, 一定还能继续向前翻
+        let next_cursor = match direction {
+            PageDirection::Forward if has_more => cursor_of(rows.last()),
+            PageDirection::Forward => None,
+            PageDirection::Backward if has_cursor => cursor_of(rows.last()),
+            PageDirection::Backward => None,
+        };
+        let prev_cursor = cursor_of(rows.first());
+
+        Ok(RecordPage {
+            records: rows.into_iter().map(|(record, _)| record).collect(),
+            next_cursor,
+            prev_cursor,
+        })
+    }
+
+    pub fn get_record_value(&self, id: i64) -> Result<String> {
+        self.read(|conn| {
+            conn.query_row(
+                "SELECT value FROM record WHERE id = ?1 AND deleted_at IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+        })?
+        .ok_or(Error::RecordNotFound(id))
+    }
+
+    // 获取完整的记录(包括图片的原始 base64), 已删除的记录视为不存在
+    pub fn get_record(&self, id: i64) -> Result<Record> {
+        self.read(|conn| {
+            conn.query_row(
+                &format!(
+                    "SELECT {} FROM record WHERE id = ?1 AND deleted_at IS NULL",
+                    RECORD_COLUMNS
+                ),
+                [id],
+                record_from_row,
+            )
+            .optional()
+        })?
+        .ok_or(Error::RecordNotFound(id))
+    }
+
+    // 从面板选择并写入剪贴板后调用, 记录移到列表最前面
+    pub fn record_paste(&self, id: i64) -> Result<()> {
+        let conn = self.writer();
+        let updated = conn.execute(
+            "UPDATE record SET paste_count = paste_count + 1,
+                 updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+             WHERE id = ?1 AND deleted_at IS NULL",
             [id],
+        )?;
+        if updated == 0 {
+            return Err(Error::RecordNotFound(id));
+        }
+        track_usage(&conn, id, PASTE_WEIGHT)?;
+        track_change(&conn, id)?;
+        Ok(())
+    }
+
+    pub fn toggle_favorite(&self, id: i64) -> Result<()> {
+        self.write(|conn| {
+            conn.execute(
+                "UPDATE record SET favorite = NOT favorite,
+                     favorite_at = strftime('%Y-%m-%d %H:%M:%f', 'now')
+                 WHERE id = ?1",
+                [id],
+            )?;
+            track_change(conn, id)
+        })
+    }
+
+    // 修改文本记录的内容, 修改前的内容保存为历史版本
+    pub fn update_record(&self, id: i64, value: String) -> Result<()> {
+        let conn = self.writer();
+
+        let (record_type, old_value): (String, String) = conn
+            .query_row(
+                "SELECT record_type, value FROM record WHERE id = ?1",
+                [id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?
+            .ok_or(Error::RecordNotFound(id))?;
+
+        if record_type != "text" {
+            return Err(Error::Unsupported(format!(
+                "Unsupported record type: {}",
+                record_type
+            )));
+        }
+        if old_value == value {
+            return Ok(());
+        }
+
+        let classification = classify(&value);
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "INSERT INTO record_revision (record_id, value) VALUES (?1, ?2)",
+            (id, &old_value),
+        )?;
+        // 修改后的内容与其他记录相同时会违反唯一索引
+        tx.execute(
+            "UPDATE record SET value = ?1, hash = ?2, subtype = ?3, language = ?4, highlight_html = NULL,
+                 updated_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE id = ?5",
+            (
+                &value,
+                content_hash(&value),
+                classification.subtype,
+                classification.language,
+                id,
+            ),
         )
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| match e.sqlite_error_code() {
+            Some(rusqlite::ErrorCode::ConstraintViolation) => {
+                Error::InvalidInput("A record with the same content already exists".to_string())
+            }
+            _ => Error::from(e),
+        })?;
+        track_edit(&tx, id, &content_hash(&old_value))?;
+        tx.commit()?;
 
-    Ok(())
+        Ok(())
+    }
+
+    pub fn get_record_revisions(&self, id: i64) -> Result<Vec<Revision>> {
+        self.read(|conn| {
+            let mut stmt = conn.prepare(
+                "SELECT id, value, created_at FROM record_revision
+                 WHERE record_id = ?1 ORDER BY id DESC",
+            )?;
+            let revisions = stmt
+                .query_map([id], |row| {
+                    Ok(Revision {
+                        id: row.get(0)?,
+                        value: row.get(1)?,
+                        created_at: row.get(2)?,
+                    })
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(revisions)
+        })
+    }
+
+    // 软删除指定记录, 收藏的记录默认不删除, 返回实际删除的 id
+    pub fn soft_delete(&self, ids: &[i64], include_favorite: bool) -> Result<Vec<i64>> {
+        if ids.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let placeholders = vec!["?"; ids.len()].join(", ");
+        let favorite_condition = if include_favorite {
+            ""
+        } else {
+            " AND favorite = 0"
+        };
+        let params_slice: Vec<&dyn rusqlite::ToSql> =
+            ids.iter().map(|id| id as &dyn rusqlite::ToSql).collect();
+
+        let condition = format!(
+            "id IN ({}) AND deleted_at IS NULL{}",
+            placeholders, favorite_condition
+        );
+
+        let conn = self.writer();
+        let tx = conn.unchecked_transaction()?;
+        let deleted = {
+            let mut stmt = tx.prepare(&format!("SELECT id FROM record WHERE {}", condition))?;
+            let deleted = stmt
+                .query_map(params_slice.as_slice(), |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+            deleted
+        };
+        track_deletes(&tx, &condition, params_slice.as_slice())?;
+        tx.execute(
+            &format!(
+                "UPDATE record SET deleted_at = strftime('%Y-%m-%d %H:%M:%f', 'now') WHERE {}",
+                condition
+            ),
+            params_slice.as_slice(),
+        )?;
+        tx.commit()?;
+
+        Ok(deleted)
+    }
+
+    // 符合查询条件的记录 id, 不分页
+    pub fn find_ids(&self, params: QueryParams) -> Result<Vec<i64>> {
+        let (conditions, query_params) = build_conditions(params)?;
+        let query = format!("SELECT id FROM record WHERE {}", conditions.join(" AND "));
+        let params_slice: Vec<&dyn rusqlite::ToSql> =
+            query_params.iter().map(|p| p.as_ref()).collect();
+
+        self.read(|conn| {
+            let mut stmt = conn.prepare(&query)?;
+            let ids = stmt
+                .query_map(params_slice.as_slice(), |row| row.get(0))?
+                .collect::<Result<Vec<i64>, _>>()?;
+            Ok(ids)
+        })
+    }
+
+    // 软删除符合查询条件的记录
+    pub fn delete_by_filter(
+        &self,
+        params: QueryParams,
+        include_favorite: bool,
+    ) -> Result<Vec<i64>> {
+        let ids = self.find_ids(params)?;
+        self.soft_delete(&ids, include_favorite)
+    }
+
+    // 撤销删除, 只对尚未清理的记录有效
+    pub fn restore_records(&self, ids: &[i64]) -> Result<()> {
+        self.write(|conn| {
+            let tx = conn.unchecked_transaction()?;
+            for id in ids {
+                let restored =
+                    tx.execute("UPDATE record SET deleted_at = NULL WHERE id = ?1", [id])?;
+                // 删除可能已经同步到其他设备, 需要重新推送
+                if restored > 0 {
+                    track_change(&tx, *id)?;
+                }
+            }
+            tx.commit()
+        })
+    }
+
+    // 真正删除超过撤销时间的记录
+    pub fn purge_deleted(&self) -> Result<usize> {
+        let conn = self.writer();
+
+        let deleted = conn.execute(
+            "DELETE FROM record
+                 WHERE deleted_at IS NOT NULL
+                     AND deleted_at <= strftime('%Y-%m-%d %H:%M:%f', 'now', ?1)",
+            [format!("-{} seconds", UNDO_WINDOW_SECS)],
+        )?;
+        if deleted > 0 {
+            purge_revisions(&conn)?;
+            purge_link_previews(&conn)?;
+            gc_blobs(&conn, &self.blobs)?;
+        }
+
+        Ok(deleted)
+    }
+
+    // 设置记录在 ttl_secs 秒后过期, None 表示取消过期
+    pub fn set_expiry(&self, id: i64, ttl_secs: Option<u64>) -> Result<()> {
+        if ttl_secs == Some(0) {
+            return Err(Error::InvalidInput("TTL must be positive".to_string()));
+        }
+        let updated = self.write(|conn| {
+            conn.execute(
+                "UPDATE record SET expires_at = CASE WHEN ?2 IS NULL THEN NULL
+                     ELSE strftime('%Y-%m-%d %H:%M:%f', 'now', '+' || ?2 || ' seconds') END
+                 WHERE id = ?1 AND deleted_at IS NULL",
+                (id, ttl_secs.map(|secs| secs as i64)),
+            )
+        })?;
+        if updated == 0 {
+            return Err(Error::RecordNotFound(id));
+        }
+        Ok(())
+    }
+
+    // 直接删除已过期的记录, 不经过软删除, 返回被删除的记录(包括图片的原始 base64)
+    pub fn purge_expired(&self) -> Result<Vec<Record>> {
+        let conn = self.writer();
+        let tx = conn.unchecked_transaction()?;
+
+        let now: String =
+            tx.query_row("SELECT strftime('%Y-%m-%d %H:%M:%f', 'now')", [], |row| {
+                row.get(0)
+            })?;
+        let expired = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT {} FROM record WHERE expires_at IS NOT NULL AND expires_at <= ?1",
+                RECORD_COLUMNS
+            ))?;
+            let expired = stmt
+                .query_map([&now], record_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            expired
+        };
+        if expired.is_empty() {
+            return Ok(expired);
+        }
+
+        let condition = "expires_at IS NOT NULL AND expires_at <= ?1";
+        track_deletes(&tx, condition, &[&now])?;
+        tx.execute(&format!("DELETE FROM record WHERE {}", condition), [&now])?;
+        purge_revisions(&tx)?;
+        purge_link_previews(&tx)?;
+        tx.commit()?;
+        gc_blobs(&conn, &self.blobs)?;
+
+        Ok(expired)
+    }
+
+    // 导出全部记录(包括图片的原始 base64)
+    pub fn export_records(&self) -> Result<Vec<Record>> {
+        self.read(|conn| {
+            let mut stmt = conn.prepare(&format!(
+                "SELECT {} FROM record WHERE deleted_at IS NULL ORDER BY updated_at DESC",
+                RECORD_COLUMNS
+            ))?;
+            let records = stmt
+                .query_map([], record_from_row)?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(records)
+        })
+    }
+
+    pub fn clear_history(&self) -> Result<()> {
+        let conn = self.writer();
+
+        let condition = "id NOT IN (SELECT id FROM record ORDER BY updated_at DESC LIMIT 1)";
+        let tx = conn.unchecked_transaction()?;
+        track_deletes(&tx, condition, &[])?;
+        tx.execute(&format!("DELETE FROM record WHERE {}", condition), [])?;
+        tx.commit()?;
+        purge_revisions(&conn)?;
+        purge_link_previews(&conn)?;
+        gc_blobs(&conn, &self.blobs)?;
+
+        Ok(())
+    }
 }
 
-pub async fn clear_history() -> Result<(), String> {
-    let db = Database::get().map_err(|e| e.to_string())?;
-    let db = db.as_ref().unwrap();
+#[tauri::command]
+pub async fn add_record(db: State<'_, Database>, record: RecordInput) -> Result<i64> {
+    db.run(move |db| db.add_record(record)).await
+}
 
-    db.conn
-        .execute(
-            "DELETE FROM record WHERE id NOT IN (
-                SELECT id FROM record 
-                ORDER BY updated_at DESC LIMIT 1
-            )",
-            [],
-        )
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn get_records(db: State<'_, Database>, params: QueryParams) -> Result<RecordPage> {
+    db.run(move |db| db.get_records(params)).await
+}
+
+#[tauri::command]
+pub async fn toggle_favorite(db: State<'_, Database>, id: i64) -> Result<()> {
+    db.run(move |db| db.toggle_favorite(id)).await
+}
+
+#[derive(Debug, Serialize)]
+pub struct Revision {
+    id: i64,
+    value: String,
+    created_at: String,
+}
 
+#[tauri::command]
+pub async fn update_record(db: State<'_, Database>, id: i64, value: String) -> Result<()> {
+    db.run(move |db| db.update_record(id, value)).await
+}
+
+#[tauri::command]
+pub async fn get_record_revisions(db: State<'_, Database>, id: i64) -> Result<Vec<Revision>> {
+    db.run(move |db| db.get_record_revisions(id)).await
+}
+
+#[tauri::command]
+pub async fn set_record_expiry(
+    db: State<'_, Database>,
+    id: i64,
+    ttl_secs: Option<u64>,
+) -> Result<()> {
+    db.run(move |db| db.set_expiry(id, ttl_secs)).await
+}
+
+// 添加用户手动输入的文本片段, 不经过剪贴板
+#[tauri::command]
+pub async fn create_snippet(db: State<'_, Database>, value: String) -> Result<i64> {
+    if value.is_empty() {
+        return Err(Error::InvalidInput("Snippet is empty".to_string()));
+    }
+
+    let record = RecordInput {
+        record_type: "text".to_string(),
+        value,
+        thumbnail_key: None,
+        preview_key: None,
+        size: None,
+        img_size: None,
+        phash: None,
+        source_app: None,
+    };
+    db.run(move |db| db.add_record(record)).await
+}
+
+// 清理已删除记录的历史版本
+fn purge_revisions(conn: &Connection) -> Result<usize, rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM record_revision WHERE record_id NOT IN (SELECT id FROM record)",
+        [],
+    )
+}
+
+// 删除已经没有对应记录的链接预览
+fn purge_link_previews(conn: &Connection) -> Result<usize, rusqlite::Error> {
+    conn.execute(
+        "DELETE FROM link_preview
+         WHERE url NOT IN (SELECT TRIM(value) FROM record WHERE record_type = 'text')",
+        [],
+    )
+}
+
+// 删除后可撤销的时间, 单位秒
+const UNDO_WINDOW_SECS: i64 = 30;
+
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct RecordsPayload {
+    pub ids: Vec<i64>,
+}
+
+#[tauri::command]
+pub async fn delete_records(
+    app: AppHandle,
+    db: State<'_, Database>,
+    ids: Vec<i64>,
+    include_favorite: Option<bool>,
+) -> Result<Vec<i64>> {
+    let deleted = db
+        .run(move |db| db.soft_delete(&ids, include_favorite.unwrap_or(false)))
+        .await?;
+    // 通知渲染进程刷新数据
+    let _ = app.emit(
+        "records-deleted",
+        RecordsPayload {
+            ids: deleted.clone(),
+        },
+    );
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub async fn delete_by_filter(
+    app: AppHandle,
+    db: State<'_, Database>,
+    params: QueryParams,
+    include_favorite: Option<bool>,
+) -> Result<Vec<i64>> {
+    let deleted = db
+        .run(move |db| db.delete_by_filter(params, include_favorite.unwrap_or(false)))
+        .await?;
+    let _ = app.emit(
+        "records-deleted",
+        RecordsPayload {
+            ids: deleted.clone(),
+        },
+    );
+    Ok(deleted)
+}
+
+#[tauri::command]
+pub async fn restore_records(app: AppHandle, db: State<'_, Database>, ids: Vec<i64>) -> Result<()> {
+    let ids = db
+        .run(move |db| db.restore_records(&ids).map(|_| ids))
+        .await?;
+
+    let _ = app.emit("records-restored", RecordsPayload { ids });
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = init_at(dir.path()).unwrap();
+        (dir, db)
+    }
+
+    fn ids(page: &RecordPage) -> Vec<i64> {
+        page.records.iter().map(|r| r.id).collect()
+    }
+
+    fn page(
+        db: &Database,
+        sort: SortBy,
+        cursor: Option<String>,
+        direction: PageDirection,
+        limit: u32,
+    ) -> RecordPage {
+        db.get_records(QueryParams {
+            sort,
+            cursor,
+            direction,
+            limit,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    // 10 条记录, 排序字段两两相同
+    fn add_ties(db: &Database) -> Vec<i64> {
+        let ids: Vec<i64> = (0..10)
+            .map(|i| {
+                db.add_record(RecordInput::text(&format!("r{}", i)))
+                    .unwrap()
+            })
+            .collect();
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET updated_at = '2026-01-0' || (id / 2 + 1) || ' 00:00:00.000',
+                     copy_count = id / 2, frecency = (id / 2) * 0.37 + 0.1,
+                     value = substr('xxxxxxxxxx', 1, id / 2 + 1)",
+                [],
+            )
+        })
+        .unwrap();
+        ids
+    }
+
+    // 按排序字段和 id 从大到小的完整顺序
+    fn expected_order(db: &Database, sort: SortBy) -> Vec<i64> {
+        let query = format!(
+            "SELECT id FROM record ORDER BY {} DESC, id DESC",
+            sort.key_expr()
+        );
+        db.read(|conn| {
+            conn.prepare(&query)?
+                .query_map([], |row| row.get(0))?
+                .collect()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn paging_with_ties() {
+        let (_dir, db) = open();
+        add_ties(&db);
+
+        for sort in [
+            SortBy::Recent,
+            SortBy::Frequency,
+            SortBy::Size,
+            SortBy::Frecency,
+        ] {
+            let expected = expected_order(&db, sort);
+            for limit in [1, 2, 3, 4] {
+                // 向前翻到最后一页, 限制次数避免游标有问题时死循环
+                let mut seen = Vec::new();
+                let mut cursor = None;
+                let mut last = None;
+                for _ in 0..=expected.len() {
+                    let p = page(&db, sort, cursor, PageDirection::Forward, limit);
+                    seen.extend(ids(&p));
+                    cursor = p.next_cursor.clone();
+                    last = Some(p);
+                    if cursor.is_none() {
+                        break;
+                    }
+                }
+                assert_eq!(seen, expected, "{:?} forward, limit {}", sort, limit);
+
+                // 从最后一页向后翻回第一页
+                let last = last.unwrap();
+                let mut back = ids(&last);
+                let mut cursor = last.prev_cursor;
+                for _ in 0..=expected.len() {
+                    let p = page(&db, sort, cursor, PageDirection::Backward, limit);
+                    let mut page_ids = ids(&p);
+                    page_ids.extend(back);
+                    back = page_ids;
+                    if p.records.len() < limit as usize {
+                        break;
+                    }
+                    cursor = p.prev_cursor;
+                }
+                assert_eq!(back, expected, "{:?} backward, limit {}", sort, limit);
+            }
+        }
+    }
+
+    #[test]
+    fn cursor_is_bound_to_sort() {
+        let (_dir, db) = open();
+        add_ties(&db);
+
+        let first = page(&db, SortBy::Recent, None, PageDirection::Forward, 3);
+        let result = db.get_records(QueryParams {
+            sort: SortBy::Frequency,
+            cursor: first.next_cursor,
+            limit: 3,
+            ..Default::default()
+        });
+        assert!(matches!(result, Err(Error::InvalidInput(_))));
+
+        for cursor in ["", "bad", "e30"] {
+            let result = db.get_records(QueryParams {
+                cursor: Some(cursor.to_string()),
+                limit: 3,
+                ..Default::default()
+            });
+            assert!(matches!(result, Err(Error::InvalidInput(_))), "{}", cursor);
+        }
+    }
+
+    #[test]
+    fn frecency_cursor_round_trip() {
+        for value in [0.1 + 0.2, 5.0 * 0.37 + 0.1, 2.0, -1.5e-7, 1234.000001] {
+            let cursor = encode_cursor(SortBy::Frecency, &SqlValue::Real(value), 7);
+            let (key, id) = decode_cursor(&cursor, SortBy::Frecency).unwrap();
+            assert_eq!(key, SqlValue::Real(value));
+            assert_eq!(id, 7);
+        }
+        let cursor = encode_cursor(SortBy::Frequency, &SqlValue::Integer(3), 1);
+        assert_eq!(
+            decode_cursor(&cursor, SortBy::Frequency).unwrap(),
+            (SqlValue::Integer(3), 1)
+        );
+        let cursor = encode_cursor(
+            SortBy::Recent,
+            &SqlValue::Text("2026-01-01 00:00:00.000".to_string()),
+            1,
+        );
+        assert!(decode_cursor(&cursor, SortBy::Recent).is_ok());
+    }
+
+    #[test]
+    fn limit_defaults_and_rejects_zero() {
+        let (_dir, db) = open();
+        for i in 0..60 {
+            db.add_record(RecordInput::text(&format!("r{}", i)))
+                .unwrap();
+        }
+
+        let params: QueryParams = serde_json::from_str("{}").unwrap();
+        assert_eq!(params.limit, DEFAULT_PAGE_SIZE);
+        assert_eq!(
+            db.get_records(params).unwrap().records.len(),
+            DEFAULT_PAGE_SIZE as usize
+        );
+
+        let params: QueryParams = serde_json::from_str(r#"{"limit": 0}"#).unwrap();
+        assert!(matches!(
+            db.get_records(params),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+
+    struct Fixture {
+        url: i64,
+        code: i64,
+        plain: i64,
+        image: i64,
+        tall_image: i64,
+        pdf: i64,
+        png: i64,
+    }
+
+    fn add_fixture(db: &Database) -> Fixture {
+        let fixture = Fixture {
+            url: db
+                .add_record(RecordInput {
+                    source_app: Some("com.apple.Safari".to_string()),
+                    ..RecordInput::text("https://example.com/a_b")
+                })
+                .unwrap(),
+            code: db
+                .add_record(RecordInput {
+                    source_app: Some("com.microsoft.VSCode".to_string()),
+                    ..RecordInput::text("pub fn main() {\n    let mut x = 1;\n}")
+                })
+                .unwrap(),
+            plain: db
+                .add_record(RecordInput::text("hello 100% world"))
+                .unwrap(),
+            image: db
+                .add_record(RecordInput {
+                    img_size: Some("200x100".to_string()),
+                    ..RecordInput::text("aGVsbG8gd29ybGQ=").with_type("image")
+                })
+                .unwrap(),
+            tall_image: db
+                .add_record(RecordInput {
+                    img_size: Some("50x400".to_string()),
+                    ..RecordInput::text("dGFsbA==").with_type("image")
+                })
+                .unwrap(),
+            pdf: db
+                .add_record(RecordInput {
+                    size: Some(2048),
+                    ..RecordInput::text("/tmp/report.pdf").with_type("file")
+                })
+                .unwrap(),
+            png: db
+                .add_record(RecordInput {
+                    size: Some(10),
+                    ..RecordInput::text("/tmp/A_B.PNG").with_type("file")
+                })
+                .unwrap(),
+        };
+        db.toggle_favorite(fixture.plain).unwrap();
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET ocr_text = 'invoice total' WHERE id = ?1",
+                [fixture.image],
+            )?;
+            // 第 n 条记录创建于 1 月 n 日, 更新于 2 月 n 日
+            conn.execute(
+                "UPDATE record SET
+                     created_at = printf('2026-01-%02d 12:00:00.000', id),
+                     updated_at = printf('2026-02-%02d 12:00:00.000', id)",
+                [],
+            )
+        })
+        .unwrap();
+        fixture
+    }
+
+    fn find(db: &Database, params: QueryParams) -> Vec<i64> {
+        let mut ids = db.find_ids(params).unwrap();
+        ids.sort();
+        ids
+    }
+
+    fn sorted(mut ids: Vec<i64>) -> Vec<i64> {
+        ids.sort();
+        ids
+    }
+
+    fn date(value: &str) -> Option<String> {
+        Some(value.to_string())
+    }
+
+    #[test]
+    fn filter_by_keyword() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let keyword = |keyword: &str| QueryParams {
+            keyword: Some(keyword.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, keyword("example")), vec![f.url]);
+        // 图片按识别出的文字搜索, 不匹配 base64
+        assert_eq!(find(&db, keyword("invoice")), vec![f.image]);
+        assert_eq!(find(&db, keyword("aGVsbG8")), Vec::<i64>::new());
+        assert_eq!(find(&db, keyword("report.pdf")), vec![f.pdf]);
+        assert_eq!(find(&db, keyword("100%")), vec![f.plain]);
+        assert_eq!(find(&db, keyword("%")), vec![f.plain]);
+        assert_eq!(find(&db, keyword("")).len(), 7);
+    }
+
+    #[test]
+    fn filter_by_type_and_classification() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let by_type = |record_type: &str| QueryParams {
+            record_type: Some(record_type.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, by_type("image")), vec![f.image, f.tall_image]);
+        assert_eq!(find(&db, by_type("all")).len(), 7);
+        let params = QueryParams {
+            record_type: Some("file".to_string()),
+            record_types: vec!["image".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.image, f.tall_image, f.pdf, f.png]);
+
+        let params = QueryParams {
+            favorite: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.plain]);
+        let params = QueryParams {
+            favorite: Some(false),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params).len(), 7);
+
+        let params = QueryParams {
+            subtype: Some("url".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.url]);
+        let params = QueryParams {
+            subtype: Some("code".to_string()),
+            language: Some("rust".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.code]);
+        let params = QueryParams {
+            language: Some("python".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), Vec::<i64>::new());
+    }
+
+    #[test]
+    fn filter_by_date() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        // 第 n 条记录创建于 1 月 n 日, 边界包含在内
+        let params = QueryParams {
+            created_after: date("2026-01-02 12:00:00"),
+            created_before: date("2026-01-04"),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.code, f.plain]);
+        let params = QueryParams {
+            updated_after: date("2026-02-06T00:00"),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.pdf, f.png]);
+        let params = QueryParams {
+            updated_before: date("2026-02-01 12:00:00.000"),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.url]);
+    }
+
+    #[test]
+    fn invalid_dates_are_rejected() {
+        let (_dir, db) = open();
+        for value in [
+            "",
+            "yesterday",
+            "now",
+            "2026-1-1",
+            "2026-13-01",
+            "2026-02-29",
+            "2026-01-01 24:00",
+            "2026-01-01 12",
+            "2026-01-01 12:00:60",
+            "2026-01-01 12:00:00.",
+            "2026-01-01 12:00:00Z",
+        ] {
+            for params in [
+                QueryParams {
+                    created_after: date(value),
+                    ..Default::default()
+                },
+                QueryParams {
+                    created_before: date(value),
+                    ..Default::default()
+                },
+                QueryParams {
+                    updated_after: date(value),
+                    ..Default::default()
+                },
+                QueryParams {
+                    updated_before: date(value),
+                    ..Default::default()
+                },
+            ] {
+                assert!(
+                    matches!(db.find_ids(params), Err(Error::InvalidInput(_))),
+                    "{}",
+                    value
+                );
+            }
+        }
+        for value in ["2024-02-29", "2026-12-31 23:59", "2026-01-01T00:00:00.5"] {
+            assert!(is_valid_datetime(value), "{}", value);
+        }
+    }
+
+    #[test]
+    fn filter_by_size_and_dimensions() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let params = QueryParams {
+            min_size: Some(1000),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.pdf]);
+        // 图片按 base64 解码后的大小计算
+        let params = QueryParams {
+            max_size: Some(8),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.tall_image]);
+
+        let params = QueryParams {
+            min_width: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.image]);
+        let params = QueryParams {
+            max_width: Some(100),
+            min_height: Some(200),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.tall_image]);
+        let params = QueryParams {
+            max_height: Some(100),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.image]);
+    }
+
+    #[test]
+    fn filter_by_extension_and_source_app() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let extensions = |extensions: &[&str]| QueryParams {
+            extensions: extensions.iter().map(|e| e.to_string()).collect(),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, extensions(&["pdf"])), vec![f.pdf]);
+        // 忽略大小写和开头的点
+        assert_eq!(find(&db, extensions(&[".png", "PDF"])), vec![f.pdf, f.png]);
+        // _ 不作为通配符
+        assert_eq!(find(&db, extensions(&["p_f"])), Vec::<i64>::new());
+        assert_eq!(find(&db, extensions(&[" ", "."])).len(), 7);
+
+        let params = QueryParams {
+            source_apps: vec![
+                "com.apple.Safari".to_string(),
+                "com.microsoft.VSCode".to_string(),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.url, f.code]);
+    }
+
+    #[test]
+    fn combined_filters() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let params = QueryParams {
+            record_types: vec!["text".to_string(), "file".to_string()],
+            keyword: Some("_".to_string()),
+            created_after: date("2026-01-02"),
+            ..Default::default()
+        };
+        // keyword 中的 _ 按字面匹配
+        assert_eq!(find(&db, params), vec![f.png]);
+
+        let params = QueryParams {
+            record_type: Some("image".to_string()),
+            keyword: Some("total".to_string()),
+            min_width: Some(150),
+            updated_before: date("2026-02-04 12:00:00"),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), vec![f.image]);
+
+        let params = QueryParams {
+            subtype: Some("url".to_string()),
+            favorite: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(find(&db, params), Vec::<i64>::new());
+
+        // get_records 使用同样的条件
+        let page = db
+            .get_records(QueryParams {
+                record_types: vec!["file".to_string()],
+                min_size: Some(1),
+                limit: 10,
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(sorted(ids(&page)), vec![f.pdf, f.png]);
+    }
+
+    #[test]
+    fn deleted_and_expired_are_excluded() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        db.soft_delete(&[f.url], false).unwrap();
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET expires_at = '2000-01-01 00:00:00.000' WHERE id = ?1",
+                [f.code],
+            )
+        })
+        .unwrap();
+        db.set_expiry(f.plain, Some(3600)).unwrap();
+
+        let ids = find(&db, QueryParams::default());
+        assert_eq!(ids, vec![f.plain, f.image, f.tall_image, f.pdf, f.png]);
+    }
+
+    fn deleted_at(db: &Database, id: i64) -> Option<String> {
+        db.read(|conn| {
+            conn.query_row("SELECT deleted_at FROM record WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn delete_keeps_favorites_unless_included() {
+        let (_dir, db) = open();
+        let plain = db.add_record(RecordInput::text("plain")).unwrap();
+        let favorite = db.add_record(RecordInput::text("favorite")).unwrap();
+        db.toggle_favorite(favorite).unwrap();
+
+        assert_eq!(
+            db.soft_delete(&[plain, favorite], false).unwrap(),
+            vec![plain]
+        );
+        assert!(matches!(
+            db.get_record(plain),
+            Err(Error::RecordNotFound(_))
+        ));
+        assert!(matches!(
+            db.get_record_value(plain),
+            Err(Error::RecordNotFound(_))
+        ));
+        assert_eq!(db.get_record_value(favorite).unwrap(), "favorite");
+        // 毫秒精度, 和其他时间字段的格式一致
+        assert_eq!(deleted_at(&db, plain).unwrap().len(), 23);
+
+        // 已删除的记录不会再次删除
+        assert_eq!(
+            db.soft_delete(&[plain, favorite], true).unwrap(),
+            vec![favorite]
+        );
+        assert!(db.get_record(favorite).is_err());
+    }
+
+    #[test]
+    fn delete_by_filter_only_deletes_matching() {
+        let (_dir, db) = open();
+        let f = add_fixture(&db);
+        let keyword = |keyword: &str| QueryParams {
+            keyword: Some(keyword.to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            db.delete_by_filter(keyword("example.com"), false).unwrap(),
+            vec![f.url]
+        );
+        // plain 是收藏
+        assert!(db
+            .delete_by_filter(keyword("100%"), false)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            db.delete_by_filter(keyword("100%"), true).unwrap(),
+            vec![f.plain]
+        );
+        assert_eq!(
+            find(&db, QueryParams::default()),
+            vec![f.code, f.image, f.tall_image, f.pdf, f.png]
+        );
+    }
+
+    #[test]
+    fn restore_and_purge_deleted() {
+        let (_dir, db) = open();
+        let kept = db.add_record(RecordInput::text("kept")).unwrap();
+        let restored = db.add_record(RecordInput::text("restored")).unwrap();
+        let purged = db.add_record(RecordInput::text("purged")).unwrap();
+        db.soft_delete(&[restored, purged], false).unwrap();
+
+        // 撤销时间内的记录不清理, 可以恢复
+        assert_eq!(db.purge_deleted().unwrap(), 0);
+        db.restore_records(&[restored]).unwrap();
+        assert_eq!(db.get_record_value(restored).unwrap(), "restored");
+        assert_eq!(deleted_at(&db, restored), None);
+
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET deleted_at = strftime('%Y-%m-%d %H:%M:%f', 'now', ?2)
+                 WHERE id = ?1",
+                rusqlite::params![purged, format!("-{} seconds", UNDO_WINDOW_SECS + 1)],
+            )
+        })
+        .unwrap();
+        assert_eq!(db.purge_deleted().unwrap(), 1);
+        // 清理后无法再恢复
+        db.restore_records(&[purged]).unwrap();
+        assert!(db.get_record(purged).is_err());
+        assert_eq!(find(&db, QueryParams::default()), vec![kept, restored]);
+    }
+
+    #[test]
+    fn similar_images_are_merged_by_default_only_when_identical() {
+        let (_dir, db) = open();
+        let id = db
+            .add_record(RecordInput::image("original", 0b1010, "1000x500"))
+            .unwrap();
+
+        // 同一张图缩放或转换格式后哈希一致
+        assert_eq!(
+            db.add_record(RecordInput::image("scaled", 0b1010, "500x250"))
+                .unwrap(),
+            id
+        );
+        // 默认不合并哈希只差一位的图片
+        assert_ne!(
+            db.add_record(RecordInput::image("changed", 0b1011, "1000x500"))
+                .unwrap(),
+            id
+        );
+        // 宽高比不同的图片即使哈希一致也不合并
+        assert_ne!(
+            db.add_record(RecordInput::image("cropped", 0b1010, "500x500"))
+                .unwrap(),
+            id
+        );
+        // 尺寸未知时不合并
+        let unknown = RecordInput {
+            img_size: None,
+            ..RecordInput::image("unknown", 0b1010, "")
+        };
+        assert_ne!(db.add_record(unknown).unwrap(), id);
+    }
+
+    #[test]
+    fn find_similar_image_checks_distance_and_aspect_ratio() {
+        let (_dir, db) = open();
+        let id = db
+            .add_record(RecordInput::image("original", 0b1010, "1001x500"))
+            .unwrap();
+
+        db.read(|conn| {
+            let find = |phash: u64, img_size: &str, max_distance: u32| {
+                find_similar_image(conn, phash, Some(img_size), max_distance).unwrap()
+            };
+            assert_eq!(find(0b1010, "500x250", 0), Some(id));
+            assert_eq!(find(0b1011, "500x250", 0), None);
+            assert_eq!(find(0b1011, "500x250", 4), Some(id));
+            assert_eq!(find(0b0101, "500x250", 4), Some(id));
+            assert_eq!(find(0b0101, "500x250", 3), None);
+            assert_eq!(find(0b1011, "500x260", 4), None);
+            assert_eq!(find(0b1011, "invalid", 4), None);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    fn expires_at(db: &Database, id: i64) -> Option<String> {
+        db.read(|conn| {
+            conn.query_row("SELECT expires_at FROM record WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+        })
+        .unwrap()
+    }
+
+    fn expire(db: &Database, id: i64) {
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET expires_at = '2000-01-01 00:00:00.000' WHERE id = ?1",
+                [id],
+            )
+        })
+        .unwrap();
+    }
+
+    #[test]
+    fn copying_again_reevaluates_expiry() {
+        let (_dir, db) = open();
+
+        // 有效期内重新复制保留过期时间
+        let live = db.add_record(RecordInput::text("live")).unwrap();
+        db.set_expiry(live, Some(3600)).unwrap();
+        let before = expires_at(&db, live);
+        assert!(before.is_some());
+        assert_eq!(db.add_record(RecordInput::text("live")).unwrap(), live);
+        assert_eq!(expires_at(&db, live), before);
+
+        // 已过期但还没清理的记录重新复制后不再过期
+        let expired = db.add_record(RecordInput::text("expired")).unwrap();
+        expire(&db, expired);
+        assert_eq!(
+            db.add_record(RecordInput::text("expired")).unwrap(),
+            expired
+        );
+        assert_eq!(expires_at(&db, expired), None);
+        assert_eq!(db.purge_expired().unwrap().len(), 0);
+
+        // 删除后重新复制不再过期
+        let deleted = db.add_record(RecordInput::text("deleted")).unwrap();
+        db.set_expiry(deleted, Some(3600)).unwrap();
+        db.soft_delete(&[deleted], false).unwrap();
+        assert_eq!(
+            db.add_record(RecordInput::text("deleted")).unwrap(),
+            deleted
+        );
+        assert_eq!(expires_at(&db, deleted), None);
+
+        let ids = find(&db, QueryParams::default());
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn merging_similar_image_reevaluates_expiry() {
+        let (_dir, db) = open();
+
+        let id = db
+            .add_record(RecordInput::image("original", 0b1010, "100x50"))
+            .unwrap();
+        expire(&db, id);
+        assert_eq!(
+            db.add_record(RecordInput::image("scaled", 0b1010, "200x100"))
+                .unwrap(),
+            id
+        );
+        assert_eq!(expires_at(&db, id), None);
+
+        db.set_expiry(id, Some(3600)).unwrap();
+        let before = expires_at(&db, id);
+        assert_eq!(
+            db.add_record(RecordInput::image("again", 0b1010, "50x25"))
+                .unwrap(),
+            id
+        );
+        assert_eq!(expires_at(&db, id), before);
+
+        db.soft_delete(&[id], false).unwrap();
+        assert_eq!(
+            db.add_record(RecordInput::image("restored", 0b1010, "100x50"))
+                .unwrap(),
+            id
+        );
+        assert_eq!(expires_at(&db, id), None);
+    }
+}
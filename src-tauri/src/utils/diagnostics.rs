@@ -0,0 +1,244 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, State};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+use crate::utils::{logger, settings};
+
+// 诊断包用于反馈问题, 只包含日志、脱敏后的设置和数据库统计, 不包含任何剪贴板内容
+const REDACTED: &str = "<redacted>";
+
+lazy_static! {
+    // 日志中的地址(同步地址、请求失败时错误信息中的链接)
+    static ref URL_RE: Regex = Regex::new(r#"(?i)\b[a-z][a-z0-9+.-]*://[^\s"'<>]+"#).unwrap();
+    // 日志中来自剪贴板内容或设置的字段: 链接的域名、来源应用、同步目标
+    static ref FIELD_RE: Regex =
+        Regex::new(r#"\b(host|domain|source_app|url|target)=("[^"]*"|\S+)"#).unwrap();
+}
+
+#[derive(Debug, Serialize)]
+struct DbStats {
+    record_count: i64,
+    deleted_count: i64,
+    favorite_count: i64,
+    revision_count: i64,
+    // 按记录类型和文本子类型统计的数量
+    record_types: BTreeMap<String, i64>,
+    subtypes: BTreeMap<String, i64>,
+    size_bytes: i64,
+    journal_mode: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Info {
+    app_version: String,
+    os: String,
+    arch: String,
+    created_at: String,
+    db: DbStats,
+}
+
+// 生成诊断包, 保存到下载目录, 返回文件路径
+#[tauri::command]
+pub async fn create_diagnostics_bundle(app: AppHandle, db: State<'_, Database>) -> Result<String> {
+    let dir = app.path().download_dir()?;
+    let app_version = app.package_info().version.to_string();
+    let path = db
+        .run(move |db| create_bundle(db, &dir, app_version))
+        .await?;
+    tracing::info!(path = %path.display(), "diagnostics bundle created");
+    Ok(path.to_string_lossy().into_owned())
+}
+
+fn create_bundle(db: &Database, dir: &Path, app_version: String) -> Result<PathBuf> {
+    let created_at: String = db
+        .read(|conn| conn.query_row("SELECT datetime('now', 'localtime')", [], |row| row.get(0)))?;
+    let info = Info {
+        app_version,
+        os: std::env::consts::OS.to_string(),
+        arch: std::env::consts::ARCH.to_string(),
+        created_at: created_at.clone(),
+        db: db_stats(db)?,
+    };
+
+    std::fs::create_dir_all(dir)?;
+    let file_name = format!(
+        "clippy2-diagnostics-{}.zip",
+        created_at.replace([' ', ':'], "-")
+    );
+    let path = dir.join(file_name);
+
+    let mut zip = ZipWriter::new(File::create(&path)?);
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    zip.start_file("info.json", options).map_err(zip_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&info)?)?;
+
+    let current = settings::get();
+    let secrets = secret_values(&current);
+    let settings = redacted_settings(current);
+    zip.start_file("settings.json", options)
+        .map_err(zip_error)?;
+    zip.write_all(&serde_json::to_vec_pretty(&settings)?)?;
+
+    if let Some(log_dir) = logger::log_dir() {
+        for entry in std::fs::read_dir(log_dir)?.flatten() {
+            let log_path = entry.path();
+            if !log_path.is_file() {
+                continue;
+            }
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let mut content = Vec::new();
+            File::open(&log_path)?.read_to_end(&mut content)?;
+            zip.start_file(format!("logs/{}", name), options)
+                .map_err(zip_error)?;
+            zip.write_all(redact_log(&String::from_utf8_lossy(&content), &secrets).as_bytes())?;
+        }
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(path)
+}
+
+fn zip_error(e: zip::result::ZipError) -> Error {
+    Error::Other(format!("Failed to write diagnostics bundle: {}", e))
+}
+
+fn db_stats(db: &Database) -> Result<DbStats> {
+    db.read(|conn| {
+        let count = |sql: &str| conn.query_row(sql, [], |row| row.get::<_, i64>(0));
+        let group = |sql: &str| {
+            let mut stmt = conn.prepare(sql)?;
+            let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<BTreeMap<String, i64>>>()
+        };
+
+        Ok(DbStats {
+            record_count: count("SELECT COUNT(*) FROM record WHERE deleted_at IS NULL")?,
+            deleted_count: count("SELECT COUNT(*) FROM record WHERE deleted_at IS NOT NULL")?,
+            favorite_count: count(
+                "SELECT COUNT(*) FROM record WHERE deleted_at IS NULL AND favorite = 1",
+            )?,
+            revision_count: count("SELECT COUNT(*) FROM record_revision")?,
+            record_types: group(
+                "SELECT record_type, COUNT(*) FROM record
+                 WHERE deleted_at IS NULL GROUP BY record_type",
+            )?,
+            subtypes: group(
+                "SELECT subtype, COUNT(*) FROM record
+                 WHERE deleted_at IS NULL AND subtype IS NOT NULL GROUP BY subtype",
+            )?,
+            size_bytes: count(
+                "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+            )?,
+            journal_mode: conn.query_row("PRAGMA journal_mode", [], |row| row.get(0))?,
+        })
+    })
+}
+
+// 去掉口令、token、同步地址和不抓取预览的域名, 只保留是否已配置
+fn redacted_settings(mut settings: settings::Settings) -> settings::Settings {
+    let redact = |value: &mut String| {
+        if !value.is_empty() {
+            *value = REDACTED.to_string();
+        }
+    };
+    redact(&mut settings.api.token);
+    redact(&mut settings.sync.target);
+    redact(&mut settings.sync.passphrase);
+    for value in [&mut settings.sync.username, &mut settings.sync.password]
+        .into_iter()
+        .flatten()
+    {
+        redact(value);
+    }
+    settings
+        .link_preview
+        .blocked_domains
+        .iter_mut()
+        .for_each(redact);
+    settings
+}
+
+// 设置中需要从日志里去掉的值
+fn secret_values(settings: &settings::Settings) -> Vec<String> {
+    let sync = &settings.sync;
+    [&settings.api.token, &sync.target, &sync.passphrase]
+        .into_iter()
+        .chain(sync.username.as_ref())
+        .chain(sync.password.as_ref())
+        .chain(&settings.link_preview.blocked_domains)
+        .filter(|value| !value.is_empty())
+        .cloned()
+        .collect()
+}
+
+// 日志中可能出现同步地址、链接和来源应用, 打包前替换掉
+fn redact_log(content: &str, secrets: &[String]) -> String {
+    let mut content = content.to_string();
+    for secret in secrets {
+        content = content.replace(secret.as_str(), REDACTED);
+    }
+    let content = URL_RE.replace_all(&content, REDACTED);
+    FIELD_RE
+        .replace_all(&content, format!("${{1}}={}", REDACTED))
+        .into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settings_are_redacted() {
+        let mut settings = settings::Settings::default();
+        settings.api.token = "token".to_string();
+        settings.sync.target = "https://dav.example.com/clippy".to_string();
+        settings.sync.password = Some("password".to_string());
+        settings.link_preview.blocked_domains = vec!["intranet.corp".to_string()];
+
+        let redacted = redacted_settings(settings);
+        assert_eq!(redacted.api.token, REDACTED);
+        assert_eq!(redacted.sync.target, REDACTED);
+        assert_eq!(redacted.sync.password.as_deref(), Some(REDACTED));
+        assert_eq!(redacted.sync.username, None);
+        assert_eq!(redacted.link_preview.blocked_domains, vec![REDACTED]);
+    }
+
+    #[test]
+    fn logs_are_redacted() {
+        let mut settings = settings::Settings::default();
+        settings.sync.target = "/Volumes/share/clippy".to_string();
+        settings.link_preview.blocked_domains = vec!["intranet.corp".to_string()];
+        let secrets = secret_values(&settings);
+
+        let log = [
+            "WARN sync failed error=error sending request for url (https://user@dav.example.com/a.log)",
+            "WARN failed to fetch link preview host=\"docs.example.com\" error=timeout",
+            "INFO skipped source_app=com.example.Secret",
+            "ERROR failed to read /Volumes/share/clippy/device.log for intranet.corp",
+            "INFO clipboard record captured id=1",
+        ]
+        .join("\n");
+        let redacted = redact_log(&log, &secrets);
+        for leaked in [
+            "dav.example.com",
+            "docs.example.com",
+            "com.example.Secret",
+            "/Volumes/share",
+            "intranet.corp",
+        ] {
+            assert!(!redacted.contains(leaked), "{}", redacted);
+        }
+        assert!(redacted.contains("host=<redacted>"));
+        assert!(redacted.contains("INFO clipboard record captured id=1"));
+    }
+}
@@ -0,0 +1,253 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use image::{DynamicImage, GenericImageView, ImageFormat, Rgba};
+use serde::Deserialize;
+use std::io::Cursor;
+use tauri::State;
+
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+use crate::utils::img_worker;
+use crate::utils::optimize_img::decode_img;
+use crate::utils::tasks::Tasks;
+
+// 编辑后图片的最长边
+const MAX_EDGE_SIZE: u32 = 16384;
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+pub struct Rect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactMode {
+    // 填充黑色
+    #[default]
+    Fill,
+    // 高斯模糊
+    Blur,
+}
+
+// 按顺序执行的编辑操作, 坐标都基于上一步的结果
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ImageOp {
+    Crop {
+        #[serde(flatten)]
+        rect: Rect,
+    },
+    // 顺时针旋转, 只支持 90 的倍数
+    Rotate {
+        degrees: u32,
+    },
+    FlipHorizontal,
+    FlipVertical,
+    // 等比缩放到不超过给定的宽高
+    Resize {
+        width: u32,
+        height: u32,
+    },
+    Redact {
+        #[serde(flatten)]
+        rect: Rect,
+        #[serde(default)]
+        mode: RedactMode,
+    },
+}
+
+// 编辑图片记录, 结果保存为新的记录, 返回新记录的 id
+#[tauri::command]
+pub async fn edit_image(
+    db: State<'_, Database>,
+    tasks: State<'_, Tasks>,
+    id: i64,
+    ops: Vec<ImageOp>,
+) -> Result<i64> {
+    if ops.is_empty() {
+        return Err(Error::InvalidInput("No edit operations".to_string()));
+    }
+
+    // 解码和编码比较耗时, 不占用异步运行时的线程
+    let new_id = db.run(move |db| edit(db, id, &ops)).await?;
+
+    tasks.recognize_text(new_id);
+    Ok(new_id)
+}
+
+pub fn edit(db: &Database, id: i64, ops: &[ImageOp]) -> Result<i64> {
+    let record = db.get_record(id)?;
+    if record.record_type != "image" {
+        return Err(Error::Unsupported(
+            "Only image records can be edited".to_string(),
+        ));
+    }
+
+    let img_bytes = STANDARD.decode(&record.value)?;
+    let edited = apply_ops(decode_img(&img_bytes)?, ops)?;
+
+    let mut png = Vec::new();
+    edited.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    // 编辑后的图片与原图很相似, 不能合并到原图, 否则打码的结果会丢失
+    db.add_record_exact(img_worker::image_record(db.blobs(), &png))
+}
+
+pub fn apply_ops(mut img: DynamicImage, ops: &[ImageOp]) -> Result<DynamicImage> {
+    for op in ops {
+        img = match *op {
+            ImageOp::Crop { rect } => {
+                check_rect(&img, rect)?;
+                img.crop_imm(rect.x, rect.y, rect.width, rect.height)
+            }
+            ImageOp::Rotate { degrees } => match degrees % 360 {
+                0 => img,
+                90 => img.rotate90(),
+                180 => img.rotate180(),
+                270 => img.rotate270(),
+                _ => {
+                    return Err(Error::InvalidInput(format!(
+                        "Unsupported rotation: {}",
+                        degrees
+                    )))
+                }
+            },
+            ImageOp::FlipHorizontal => img.fliph(),
+            ImageOp::FlipVertical => img.flipv(),
+            ImageOp::Resize { width, height } => {
+                if width == 0 || height == 0 || width > MAX_EDGE_SIZE || height > MAX_EDGE_SIZE {
+                    return Err(Error::InvalidInput(format!(
+                        "Invalid size: {}x{}",
+                        width, height
+                    )));
+                }
+                img.resize(width, height, image::imageops::FilterType::Lanczos3)
+            }
+            ImageOp::Redact { rect, mode } => {
+                check_rect(&img, rect)?;
+                redact(img, rect, mode)
+            }
+        };
+    }
+    Ok(img)
+}
+
+// 区域不能为空, 也不能超出图片
+fn check_rect(img: &DynamicImage, rect: Rect) -> Result<()> {
+    let (width, height) = img.dimensions();
+    let valid = rect.width > 0
+        && rect.height > 0
+        && rect.x as u64 + rect.width as u64 <= width as u64
+        && rect.y as u64 + rect.height as u64 <= height as u64;
+    if !valid {
+        return Err(Error::InvalidInput(format!(
+            "Invalid region {}x{}+{}+{} for image {}x{}",
+            rect.width, rect.height, rect.x, rect.y, width, height
+        )));
+    }
+    Ok(())
+}
+
+fn redact(img: DynamicImage, rect: Rect, mode: RedactMode) -> DynamicImage {
+    let mut img = img.to_rgba8();
+    match mode {
+        RedactMode::Fill => {
+            for y in rect.y..rect.y + rect.height {
+                for x in rect.x..rect.x + rect.width {
+                    img.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        RedactMode::Blur => {
+            let region =
+                image::imageops::crop_imm(&img, rect.x, rect.y, rect.width, rect.height).to_image();
+            // 模糊半径随区域大小变化, 保证文字无法辨认
+            let sigma = (rect.width.min(rect.height) as f32 / 4.0).max(8.0);
+            let blurred = image::imageops::blur(&region, sigma);
+            image::imageops::replace(&mut img, &blurred, rect.x as i64, rect.y as i64);
+        }
+    }
+    DynamicImage::ImageRgba8(img)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db;
+    use image::RgbaImage;
+
+    // 带渐变和色块的图片, 感知哈希有足够的特征
+    fn sample_png() -> Vec<u8> {
+        let img = RgbaImage::from_fn(160, 120, |x, y| {
+            if (40..80).contains(&x) && (30..60).contains(&y) {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([(x * 255 / 160) as u8, (y * 255 / 120) as u8, 128, 255])
+            }
+        });
+        let mut png = Vec::new();
+        DynamicImage::ImageRgba8(img)
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    fn assert_new_record(ops: &[ImageOp]) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        let id = db
+            .add_record(img_worker::image_record(db.blobs(), &sample_png()))
+            .unwrap();
+
+        let new_id = edit(&db, id, ops).unwrap();
+        assert_ne!(new_id, id);
+        assert_ne!(
+            db.get_record_value(new_id).unwrap(),
+            db.get_record_value(id).unwrap()
+        );
+        // 再次复制原图仍然合并到原记录
+        let again = db
+            .add_record(img_worker::image_record(db.blobs(), &sample_png()))
+            .unwrap();
+        assert_eq!(again, id);
+    }
+
+    #[test]
+    fn redacted_image_is_a_new_record() {
+        let rect = Rect {
+            x: 10,
+            y: 10,
+            width: 8,
+            height: 8,
+        };
+        assert_new_record(&[ImageOp::Redact {
+            rect,
+            mode: RedactMode::Fill,
+        }]);
+    }
+
+    #[test]
+    fn resized_image_is_a_new_record() {
+        assert_new_record(&[ImageOp::Resize {
+            width: 80,
+            height: 60,
+        }]);
+    }
+
+    #[test]
+    fn invalid_region_is_rejected() {
+        let img = decode_img(&sample_png()).unwrap();
+        let rect = Rect {
+            x: 150,
+            y: 0,
+            width: 20,
+            height: 10,
+        };
+        assert!(matches!(
+            apply_ops(img, &[ImageOp::Crop { rect }]),
+            Err(Error::InvalidInput(_))
+        ));
+    }
+}
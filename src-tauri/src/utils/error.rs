@@ -0,0 +1,91 @@
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+
+// 统一的错误类型, 返回给前端时序列化为 { code, message }, 前端按 code 区分处理
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Database error: {0}")]
+    Database(#[from] rusqlite::Error),
+    #[error("Database pool error: {0}")]
+    Pool(#[from] r2d2::Error),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Image error: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("HTTP error: {0}")]
+    Http(reqwest::Error),
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to decode base64: {0}")]
+    Base64(#[from] base64::DecodeError),
+    #[error("Tauri error: {0}")]
+    Tauri(#[from] tauri::Error),
+    #[error("Record {0} not found")]
+    RecordNotFound(i64),
+    #[error("File not found")]
+    FileNotFound,
+    // 参数不合法, 如图片编辑的区域超出范围
+    #[error("{0}")]
+    InvalidInput(String),
+    // 不支持的记录类型或格式
+    #[error("{0}")]
+    Unsupported(String),
+    // 调用系统剪贴板失败
+    #[error("{0}")]
+    Clipboard(String),
+    #[error("{0}")]
+    Sync(String),
+    #[error("{0}")]
+    Other(String),
+}
+
+impl Error {
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::Database(_) | Error::Pool(_) => "database",
+            Error::Io(_) => "io",
+            Error::Image(_) => "image",
+            Error::Http(_) => "http",
+            Error::Json(_) => "json",
+            Error::Base64(_) => "base64",
+            Error::Tauri(_) => "tauri",
+            Error::RecordNotFound(_) => "record_not_found",
+            Error::FileNotFound => "file_not_found",
+            Error::InvalidInput(_) => "invalid_input",
+            Error::Unsupported(_) => "unsupported",
+            Error::Clipboard(_) => "clipboard",
+            Error::Sync(_) => "sync",
+            Error::Other(_) => "other",
+        }
+    }
+}
+
+impl Serialize for Error {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("Error", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.end()
+    }
+}
+
+// 请求地址可能是复制的链接或带账号的同步地址, 不放进错误信息和日志
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Self {
+        Error::Http(e.without_url())
+    }
+}
+
+impl From<String> for Error {
+    fn from(message: String) -> Self {
+        Error::Other(message)
+    }
+}
+
+impl From<&str> for Error {
+    fn from(message: &str) -> Self {
+        Error::Other(message.to_string())
+    }
+}
+
+pub type Result<T, E = Error> = std::result::Result<T, E>;
@@ -0,0 +1,42 @@
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::utils::db::{Database, RecordsPayload};
+use crate::utils::error::Result;
+use crate::utils::{clipboard_read, clipboard_write, settings};
+
+// 验证码等临时记录的有效期通常只有几十秒, 清理间隔要短
+const SWEEP_INTERVAL: Duration = Duration::from_secs(5);
+
+pub fn init(app: &tauri::App) {
+    let app = app.handle().clone();
+    std::thread::spawn(move || loop {
+        if let Err(e) = sweep(&app) {
+            tracing::error!(error = %e, "failed to purge expired records");
+        }
+        std::thread::sleep(SWEEP_INTERVAL);
+    });
+}
+
+fn sweep(app: &AppHandle) -> Result<()> {
+    let expired = app.state::<Database>().purge_expired()?;
+    if expired.is_empty() {
+        return Ok(());
+    }
+    tracing::info!(count = expired.len(), "expired records purged");
+
+    if settings::get().expiry.clear_clipboard {
+        let held = expired
+            .iter()
+            .any(|record| clipboard_read::holds(&record.record_type, &record.value));
+        if held {
+            clipboard_write::clear()?;
+            tracing::info!("clipboard cleared for expired record");
+        }
+    }
+
+    // 通知渲染进程刷新数据
+    let ids = expired.iter().map(|record| record.id).collect();
+    let _ = app.emit("records-deleted", RecordsPayload { ids });
+    Ok(())
+}
@@ -1,4 +1,4 @@
-use crate::utils::nspanel;
+use crate::utils::{nspanel, pause};
 use tauri::Manager;
 use tauri_plugin_global_shortcut::{Code, Modifiers, ShortcutState};
 
@@ -8,11 +8,20 @@ pub fn register(app: &tauri::App) {
         tauri_plugin_global_shortcut::Builder::new()
             .with_shortcut("CommandOrControl+Shift+V")
             .unwrap()
+            // 暂停/恢复记录剪贴板
+            .with_shortcut("Command+Control+V")
+            .unwrap()
             .with_handler(|app_handle, shortcut, event| {
                 if event.state == ShortcutState::Pressed {
                     // println!("shortcut: {:#?}", shortcut);
                     if shortcut.matches(Modifiers::SHIFT | Modifiers::SUPER, Code::KeyV) {
-                        nspanel::toggle_panel(app_handle.to_owned());
+                        if let Err(e) = nspanel::toggle_panel(app_handle.to_owned()) {
+                            tracing::error!(error = %e, "failed to toggle panel");
+                        }
+                    } else if shortcut.matches(Modifiers::CONTROL | Modifiers::SUPER, Code::KeyV) {
+                        if let Err(e) = pause::toggle(app_handle) {
+                            tracing::error!(error = %e, "failed to toggle capture");
+                        }
                     }
                 }
             })
@@ -0,0 +1,112 @@
+use lazy_static::lazy_static;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use std::sync::mpsc::{self, Sender};
+use syntect::highlighting::ThemeSet;
+use syntect::html::highlighted_html_for_string;
+use syntect::parsing::{SyntaxReference, SyntaxSet};
+use tauri::State;
+
+use crate::utils::classify;
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+
+// 代码片段的语法高亮, 在后台线程中生成 HTML 并缓存到 highlight_html 列
+const THEME: &str = "InspiredGitHub";
+// 超过该长度的代码不高亮, 按纯文本显示
+const MAX_HIGHLIGHT_BYTES: usize = 256 * 1024;
+
+lazy_static! {
+    static ref SYNTAX_SET: SyntaxSet = SyntaxSet::load_defaults_newlines();
+    static ref THEME_SET: ThemeSet = ThemeSet::load_defaults();
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecordPreview {
+    pub language: Option<String>,
+    pub html: String,
+}
+
+// 启动高亮线程, 返回高亮队列, 由 Tasks 持有, 不是代码的记录会被忽略
+pub fn init(db: Database) -> Sender<i64> {
+    let (sender, receiver) = mpsc::channel::<i64>();
+
+    std::thread::spawn(move || {
+        for id in receiver {
+            if let Err(e) = load_or_render(&db, id) {
+                tracing::error!(id, error = %e, "failed to highlight record");
+            }
+        }
+    });
+    sender
+}
+
+// classify 识别出的语言对应的文件扩展名
+fn find_syntax(language: Option<&str>, code: &str) -> &'static SyntaxReference {
+    let extension = match language {
+        Some("rust") => Some("rs"),
+        Some("python") => Some("py"),
+        // 默认的语法中没有 TypeScript, 按 JavaScript 高亮
+        Some("typescript") | Some("javascript") => Some("js"),
+        Some("go") => Some("go"),
+        Some("java") => Some("java"),
+        Some("c") => Some("c"),
+        Some("cpp") => Some("cpp"),
+        Some("shell") => Some("sh"),
+        Some("sql") => Some("sql"),
+        Some("html") => Some("html"),
+        Some("css") => Some("css"),
+        _ => None,
+    };
+    extension
+        .and_then(|ext| SYNTAX_SET.find_syntax_by_extension(ext))
+        .or_else(|| SYNTAX_SET.find_syntax_by_first_line(code))
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text())
+}
+
+pub fn render(code: &str, language: Option<&str>) -> Result<String> {
+    let theme = &THEME_SET.themes[THEME];
+    highlighted_html_for_string(code, &SYNTAX_SET, find_syntax(language, code), theme)
+        .map_err(|e| Error::Other(e.to_string()))
+}
+
+// 读取缓存, 没有缓存时生成并写入, 不是代码或代码过长时返回 None
+fn load_or_render(db: &Database, id: i64) -> Result<Option<RecordPreview>> {
+    // value, subtype, language, highlight_html
+    type CodeRow = (String, Option<String>, Option<String>, Option<String>);
+    let row: Option<CodeRow> = db.read(|conn| {
+        conn.query_row(
+            "SELECT value, subtype, language, highlight_html FROM record
+                 WHERE id = ?1 AND record_type = 'text'",
+            [id],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+        )
+        .optional()
+    })?;
+
+    let Some((value, subtype, language, cached)) = row else {
+        return Ok(None);
+    };
+    if subtype.as_deref() != Some(classify::CODE) || value.len() > MAX_HIGHLIGHT_BYTES {
+        return Ok(None);
+    }
+    if let Some(html) = cached {
+        return Ok(Some(RecordPreview { language, html }));
+    }
+
+    let html = render(&value, language.as_deref())?;
+    // 生成期间内容被修改时不写入
+    db.write(|conn| {
+        conn.execute(
+            "UPDATE record SET highlight_html = ?1 WHERE id = ?2 AND value = ?3",
+            (&html, id, &value),
+        )
+    })?;
+    Ok(Some(RecordPreview { language, html }))
+}
+
+// 获取代码记录高亮后的 HTML, 尚未生成时在这里生成
+#[tauri::command]
+pub async fn get_record_preview(db: State<'_, Database>, id: i64) -> Result<Option<RecordPreview>> {
+    db.run(move |db| load_or_render(db, id)).await
+}
@@ -0,0 +1,170 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::sync::mpsc::{self, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+
+use crate::utils::blob_store::BlobStore;
+use crate::utils::db::{Database, RecordInput};
+use crate::utils::optimize_img::{optimize_img, MAX_IMG_BYTES};
+use crate::utils::tasks::Tasks;
+
+// 图片的解码和压缩比较耗时, 放到后台线程中处理, 不阻塞剪贴板轮询
+const WORKER_COUNT: usize = 2;
+// 排队中的图片数量上限
+const QUEUE_SIZE: usize = 8;
+
+// 图片数据和复制时激活的应用
+type ImageJob = (Vec<u8>, Option<String>);
+
+// 图片处理队列, 由 init 创建后交给剪贴板轮询线程
+#[derive(Clone)]
+pub struct ImageQueue {
+    sender: SyncSender<ImageJob>,
+}
+
+pub fn init(db: Database, tasks: Tasks) -> ImageQueue {
+    let (sender, receiver) = mpsc::sync_channel::<ImageJob>(QUEUE_SIZE);
+
+    let receiver = Arc::new(Mutex::new(receiver));
+    for _ in 0..WORKER_COUNT {
+        let receiver = Arc::clone(&receiver);
+        let db = db.clone();
+        let tasks = tasks.clone();
+        std::thread::spawn(move || loop {
+            let job = receiver.lock().unwrap_or_else(|e| e.into_inner()).recv();
+            let Ok((img_bytes, source_app)) = job else {
+                break;
+            };
+            // 图片库在异常数据上可能 panic, 不能让一张图片拖垮 worker
+            let blobs = db.blobs();
+            let record = std::panic::catch_unwind(|| image_record(blobs, &img_bytes))
+                .unwrap_or_else(|_| {
+                    tracing::error!("image worker panicked, saving original image");
+                    fallback_record(&img_bytes)
+                });
+            save(
+                &db,
+                &tasks,
+                RecordInput {
+                    source_app,
+                    ..record
+                },
+            );
+        });
+    }
+    ImageQueue { sender }
+}
+
+impl ImageQueue {
+    // 将图片加入处理队列, base64 编码和内容哈希都在 worker 中进行
+    // 图片超过 MAX_IMG_BYTES 或队列已满时不保存原图, 返回占位记录由调用方直接保存
+    pub fn enqueue(&self, img_bytes: &[u8], source_app: Option<String>) -> Option<RecordInput> {
+        let placeholder = |reason: &str| {
+            tracing::warn!(
+                bytes = img_bytes.len(),
+                reason,
+                "image not processed, saving a placeholder"
+            );
+            Some(RecordInput {
+                source_app: source_app.clone(),
+                ..placeholder_record(img_bytes.len())
+            })
+        };
+        if img_bytes.len() > MAX_IMG_BYTES {
+            return placeholder("too large");
+        }
+        match self
+            .sender
+            .try_send((img_bytes.to_vec(), source_app.clone()))
+        {
+            Ok(()) => None,
+            Err(TrySendError::Full(_)) => placeholder("queue full"),
+            Err(TrySendError::Disconnected(_)) => placeholder("worker stopped"),
+        }
+    }
+}
+
+fn save(db: &Database, tasks: &Tasks, record: RecordInput) {
+    match db.add_record(record) {
+        Ok(id) => tasks.recognize_text(id),
+        Err(e) => tracing::error!(error = %e, "failed to save image"),
+    }
+}
+
+// 生成缩略图, 解码失败时仍然保存原图
+pub fn image_record(blobs: &BlobStore, img_bytes: &[u8]) -> RecordInput {
+    match optimize_img(blobs, img_bytes) {
+        Ok(optimized) => RecordInput {
+            record_type: "image".to_string(),
+            value: STANDARD.encode(img_bytes),
+            thumbnail_key: Some(optimized.thumbnail_key),
+            preview_key: Some(optimized.preview_key),
+            size: None,
+            img_size: Some(optimized.img_size),
+            phash: Some(optimized.phash as i64),
+            source_app: None,
+        },
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to optimize image, saving original image");
+            fallback_record(img_bytes)
+        }
+    }
+}
+
+// 不解码图片, 只保存原图, 前端显示为无法预览
+fn fallback_record(img_bytes: &[u8]) -> RecordInput {
+    RecordInput {
+        record_type: "image".to_string(),
+        value: STANDARD.encode(img_bytes),
+        thumbnail_key: None,
+        preview_key: None,
+        size: None,
+        img_size: None,
+        phash: None,
+        source_app: None,
+    }
+}
+
+// 只记录图片大小, 不保存内容, 前端显示为无法预览, 也不能粘贴
+// 内容都为空, 多次出现时合并为同一条记录
+pub fn placeholder_record(len: usize) -> RecordInput {
+    RecordInput {
+        record_type: "image".to_string(),
+        value: String::new(),
+        thumbnail_key: None,
+        preview_key: None,
+        size: Some(len as u64),
+        img_size: None,
+        phash: None,
+        source_app: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oversized_image_becomes_placeholder() {
+        let (sender, _receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let queue = ImageQueue { sender };
+        let img_bytes = vec![0; MAX_IMG_BYTES + 1];
+        let record = queue
+            .enqueue(&img_bytes, Some("com.apple.Preview".to_string()))
+            .unwrap();
+        assert_eq!(record.record_type, "image");
+        assert!(record.value.is_empty());
+        assert_eq!(record.size, Some(MAX_IMG_BYTES as u64 + 1));
+        assert_eq!(record.source_app.as_deref(), Some("com.apple.Preview"));
+    }
+
+    #[test]
+    fn undecodable_image_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::open(dir.path()).unwrap();
+        let record = image_record(&blobs, b"not an image");
+        assert_eq!(record.value, STANDARD.encode(b"not an image"));
+        assert_eq!(record.thumbnail_key, None);
+        assert_eq!(record.size, None);
+    }
+}
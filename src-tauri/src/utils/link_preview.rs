@@ -0,0 +1,721 @@
+use lazy_static::lazy_static;
+use regex::Regex;
+use reqwest::blocking::Client;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::Url;
+use rusqlite::OptionalExtension;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, SocketAddr, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::State;
+
+use crate::utils::blob_store::BlobStore;
+use crate::utils::classify::{self, classify};
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+use crate::utils::settings;
+
+// 抓取链接页面的标题, 描述和图标, 在后台线程中排队执行
+const TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_REDIRECTS: usize = 5;
+// 只读取页面开头的部分, 标题和图标一般都在 head 中
+const MAX_PAGE_BYTES: u64 = 512 * 1024;
+const MAX_FAVICON_BYTES: u64 = 256 * 1024;
+// 标题和描述的最大长度
+const MAX_TEXT_CHARS: usize = 300;
+
+lazy_static! {
+    static ref TITLE_RE: Regex = Regex::new(r"(?is)<title[^>]*>(.*?)</title>").unwrap();
+    static ref META_RE: Regex = Regex::new(r"(?is)<meta\s[^>]*>").unwrap();
+    static ref LINK_RE: Regex = Regex::new(r"(?is)<link\s[^>]*>").unwrap();
+    static ref ATTR_RE: Regex =
+        Regex::new(r#"(?is)([a-z:-]+)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap();
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    // 图标在 blob store 中的 key
+    pub favicon_key: Option<String>,
+}
+
+// 启动抓取线程, 返回抓取队列, 由 Tasks 持有
+pub fn init(db: Database) -> Sender<String> {
+    let (sender, receiver) = mpsc::channel::<String>();
+
+    std::thread::spawn(move || {
+        let mut last_fetch: Option<Instant> = None;
+        for url in receiver {
+            let link_settings = settings::get().link_preview;
+            if !link_settings.enabled {
+                continue;
+            }
+            // 限制抓取频率
+            if let Some(last_fetch) = last_fetch {
+                let interval = Duration::from_millis(link_settings.min_interval_ms);
+                std::thread::sleep(interval.saturating_sub(last_fetch.elapsed()));
+            }
+            if process(&db, &url, &link_settings.blocked_domains) {
+                last_fetch = Some(Instant::now());
+            }
+        }
+    });
+    sender
+}
+
+// 开启了链接预览并且文本是链接时才需要抓取
+pub fn should_fetch(text: &str) -> bool {
+    settings::get().link_preview.enabled && classify(text).subtype == classify::URL
+}
+
+// 返回是否发起了请求
+fn process(db: &Database, url: &str, blocked_domains: &[String]) -> bool {
+    let cached = db
+        .read(|conn| {
+            conn.query_row("SELECT 1 FROM link_preview WHERE url = ?1", [url], |_| {
+                Ok(())
+            })
+            .optional()
+        })
+        .unwrap_or(None);
+    if cached.is_some() {
+        return false;
+    }
+
+    let preview = match fetch_preview(db.blobs(), url, blocked_domains) {
+        Ok(Some(preview)) => preview,
+        Ok(None) => return false,
+        // 失败时也缓存, 避免反复请求
+        Err(e) => {
+            // 链接属于剪贴板内容, 日志中只记录域名
+            let host = parse_url(url)
+                .ok()
+                .and_then(|url| url.host_str().map(str::to_string))
+                .unwrap_or_default();
+            tracing::warn!(host, error = %e, "failed to fetch link preview");
+            LinkPreview {
+                url: url.to_string(),
+                ..Default::default()
+            }
+        }
+    };
+
+    let _ = db.write(|conn| {
+        conn.execute(
+            "INSERT OR REPLACE INTO link_preview (url, title, description, favicon_key)
+             VALUES (?1, ?2, ?3, ?4)",
+            (
+                &preview.url,
+                &preview.title,
+                &preview.description,
+                &preview.favicon_key,
+            ),
+        )
+    });
+    true
+}
+
+fn parse_url(text: &str) -> Result<Url> {
+    let text = text.trim();
+    let url = if text.to_lowercase().starts_with("www.") {
+        Url::parse(&format!("https://{}", text))
+    } else {
+        Url::parse(text)
+    }
+    .map_err(|e| Error::InvalidInput(e.to_string()))?;
+
+    match url.scheme() {
+        "http" | "https" => Ok(url),
+        scheme => Err(Error::Unsupported(format!(
+            "Unsupported scheme: {}",
+            scheme
+        ))),
+    }
+}
+
+// 域名本身或其子域名在屏蔽列表中
+fn is_blocked(url: &Url, blocked_domains: &[String]) -> bool {
+    let Some(host) = url.host_str() else {
+        return true;
+    };
+    let host = host.to_lowercase();
+    blocked_domains.iter().any(|domain| {
+        let domain = domain.trim().trim_start_matches('.').to_lowercase();
+        !domain.is_empty() && (host == domain || host.ends_with(&format!(".{}", domain)))
+    })
+}
+
+// 只访问公网地址, 避免复制的链接(或它的重定向)访问本机和局域网中的服务
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, ..] = ip.octets();
+            !(ip.is_private()
+                || ip.is_loopback()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast()
+                || ip.is_multicast()
+                || ip.is_documentation()
+                || a == 0
+                // 100.64.0.0/10, 运营商级 NAT
+                || (a == 100 && (b & 0xc0) == 64))
+        }
+        IpAddr::V6(ip) => {
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_public_ip(IpAddr::V4(ip));
+            }
+            let segment = ip.segments()[0];
+            !(ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // fc00::/7 唯一本地地址
+                || (segment & 0xfe00) == 0xfc00
+                // fe80::/10 链路本地地址
+                || (segment & 0xffc0) == 0xfe80)
+        }
+    }
+}
+
+// 测试时允许访问本机启动的服务
+fn is_allowed_ip(ip: IpAddr, allow_loopback: bool) -> bool {
+    is_public_ip(ip) || (allow_loopback && ip.is_loopback())
+}
+
+// 链接中直接写 IP 时不经过域名解析, 需要单独检查
+fn is_allowed_host(url: &Url, allow_loopback: bool) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    match host.trim_start_matches('[').trim_end_matches(']').parse() {
+        Ok(ip) => is_allowed_ip(ip, allow_loopback),
+        Err(_) => true,
+    }
+}
+
+// 只返回允许访问的地址, 域名在检查之后重新解析到内网地址也无法访问
+struct PublicResolver {
+    allow_loopback: bool,
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let allow_loopback = self.allow_loopback;
+        let host = name.as_str().to_string();
+        // 后台线程一次只抓取一个链接, 直接使用系统的阻塞解析
+        Box::pin(async move {
+            let addrs: Vec<SocketAddr> = (host.as_str(), 0)
+                .to_socket_addrs()?
+                .filter(|addr| is_allowed_ip(addr.ip(), allow_loopback))
+                .collect();
+            if addrs.is_empty() {
+                return Err(Error::Unsupported(format!("No public address for {}", host)).into());
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// 抓取链接的预览信息, 图标保存到 blob store, 链接被屏蔽或指向内网地址时返回 None
+pub fn fetch_preview(
+    blobs: &BlobStore,
+    text: &str,
+    blocked_domains: &[String],
+) -> Result<Option<LinkPreview>> {
+    fetch(blobs, text, blocked_domains, false)
+}
+
+fn fetch(
+    blobs: &BlobStore,
+    text: &str,
+    blocked_domains: &[String],
+    allow_loopback: bool,
+) -> Result<Option<LinkPreview>> {
+    let url = parse_url(text)?;
+    if is_blocked(&url, blocked_domains) || !is_allowed_host(&url, allow_loopback) {
+        return Ok(None);
+    }
+
+    // 每次重定向都重新检查, 重定向到屏蔽的域名时停止, 指向内网地址时报错
+    let redirect_blocked_domains = blocked_domains.to_vec();
+    let redirect = Policy::custom(move |attempt| {
+        if attempt.previous().len() > MAX_REDIRECTS {
+            attempt.error("Too many redirects")
+        } else if !is_allowed_host(attempt.url(), allow_loopback) {
+            attempt.error("Redirect to a private address")
+        } else if is_blocked(attempt.url(), &redirect_blocked_domains) {
+            attempt.stop()
+        } else {
+            attempt.follow()
+        }
+    });
+    let client = Client::builder()
+        .timeout(TIMEOUT)
+        .redirect(redirect)
+        .dns_resolver(Arc::new(PublicResolver { allow_loopback }))
+        .user_agent(concat!("clippy2/", env!("CARGO_PKG_VERSION")))
+        .build()?;
+
+    let response = client.get(url).send().and_then(|r| r.error_for_status())?;
+    // 重定向到屏蔽的域名时同样不抓取
+    let page_url = response.url().clone();
+    if response.status().is_redirection() || is_blocked(&page_url, blocked_domains) {
+        return Ok(None);
+    }
+
+    let is_html = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.contains("html"));
+    if !is_html {
+        return Err(Error::Unsupported("Not an HTML page".to_string()));
+    }
+
+    let mut body = Vec::new();
+    response.take(MAX_PAGE_BYTES).read_to_end(&mut body)?;
+    let html = String::from_utf8_lossy(&body);
+    let page = parse_html(&html);
+
+    // 页面没有声明图标时尝试默认的 /favicon.ico
+    let favicon_url = page
+        .icon_href
+        .as_deref()
+        .and_then(|href| page_url.join(href).ok())
+        .or_else(|| page_url.join("/favicon.ico").ok());
+    let favicon_key = favicon_url.and_then(|favicon_url| {
+        fetch_favicon(blobs, &client, favicon_url, blocked_domains, allow_loopback)
+            .map_err(|e| tracing::warn!(error = %e, "failed to fetch favicon"))
+            .ok()
+            .flatten()
+    });
+
+    Ok(Some(LinkPreview {
+        url: text.trim().to_string(),
+        title: page.title,
+        description: page.description,
+        favicon_key,
+    }))
+}
+
+fn fetch_favicon(
+    blobs: &BlobStore,
+    client: &Client,
+    url: Url,
+    blocked_domains: &[String],
+    allow_loopback: bool,
+) -> Result<Option<String>> {
+    if !matches!(url.scheme(), "http" | "https")
+        || is_blocked(&url, blocked_domains)
+        || !is_allowed_host(&url, allow_loopback)
+    {
+        return Ok(None);
+    }
+
+    let response = client.get(url).send().and_then(|r| r.error_for_status())?;
+    if response.status().is_redirection() {
+        return Ok(None);
+    }
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+        .to_lowercase();
+
+    let mut bytes = Vec::new();
+    response.take(MAX_FAVICON_BYTES).read_to_end(&mut bytes)?;
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    let ext = match content_type.split(';').next().unwrap_or_default().trim() {
+        "image/png" => "png",
+        "image/x-icon" | "image/vnd.microsoft.icon" => "ico",
+        "image/svg+xml" => "svg",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/webp" => "webp",
+        // 部分服务器不返回正确的类型, 按内容判断
+        _ => match image::guess_format(&bytes) {
+            Ok(image::ImageFormat::Png) => "png",
+            Ok(image::ImageFormat::Ico) => "ico",
+            Ok(image::ImageFormat::Jpeg) => "jpg",
+            Ok(image::ImageFormat::Gif) => "gif",
+            Ok(image::ImageFormat::WebP) => "webp",
+            _ => return Ok(None),
+        },
+    };
+    blobs.put(&bytes, ext).map(Some)
+}
+
+#[derive(Debug, Default)]
+struct Page {
+    title: Option<String>,
+    description: Option<String>,
+    icon_href: Option<String>,
+}
+
+fn parse_attrs(tag: &str) -> HashMap<String, String> {
+    ATTR_RE
+        .captures_iter(tag)
+        .map(|caps| {
+            let value = caps
+                .get(2)
+                .or_else(|| caps.get(3))
+                .or_else(|| caps.get(4))
+                .map_or("", |m| m.as_str());
+            (caps[1].to_lowercase(), value.to_string())
+        })
+        .collect()
+}
+
+// 优先使用 og 标签
+fn parse_html(html: &str) -> Page {
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut description = None;
+    for tag in META_RE.find_iter(html) {
+        let attrs = parse_attrs(tag.as_str());
+        let Some(content) = attrs.get("content") else {
+            continue;
+        };
+        let name = attrs
+            .get("property")
+            .or_else(|| attrs.get("name"))
+            .map(|n| n.to_lowercase());
+        match name.as_deref() {
+            Some("og:title") => og_title = og_title.or(clean_text(content)),
+            Some("og:description") => og_description = og_description.or(clean_text(content)),
+            Some("description") => description = description.or(clean_text(content)),
+            _ => {}
+        }
+    }
+
+    let icon_href = LINK_RE.find_iter(html).find_map(|tag| {
+        let attrs = parse_attrs(tag.as_str());
+        let is_icon = attrs.get("rel").is_some_and(|rel| {
+            rel.split_whitespace()
+                .any(|r| r.eq_ignore_ascii_case("icon"))
+        });
+        if is_icon {
+            attrs.get("href").filter(|href| !href.is_empty()).cloned()
+        } else {
+            None
+        }
+    });
+
+    let title = og_title.or_else(|| {
+        TITLE_RE
+            .captures(html)
+            .and_then(|caps| clean_text(&caps[1]))
+    });
+
+    Page {
+        title,
+        description: og_description.or(description),
+        icon_href,
+    }
+}
+
+// 解码常见的 HTML 实体, 合并空白并截断
+fn clean_text(text: &str) -> Option<String> {
+    let decoded = text
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&");
+    let text = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    Some(text.chars().take(MAX_TEXT_CHARS).collect())
+}
+
+// 获取已缓存的链接预览, 尚未抓取或抓取失败时返回 None
+#[tauri::command]
+pub async fn get_link_preview(db: State<'_, Database>, url: String) -> Result<Option<LinkPreview>> {
+    db.run(move |db| {
+        db.read(|conn| {
+            conn.query_row(
+                "SELECT url, title, description, favicon_key FROM link_preview
+                 WHERE url = ?1 AND (title IS NOT NULL OR favicon_key IS NOT NULL)",
+                [url.trim()],
+                |row| {
+                    Ok(LinkPreview {
+                        url: row.get(0)?,
+                        title: row.get(1)?,
+                        description: row.get(2)?,
+                        favicon_key: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+        })
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    const PNG: &[u8] = &[137, 80, 78, 71, 1, 2, 3];
+
+    struct Server {
+        port: u16,
+        requests: Arc<AtomicUsize>,
+    }
+
+    impl Server {
+        fn url(&self, path: &str) -> String {
+            format!("http://127.0.0.1:{}{}", self.port, path)
+        }
+    }
+
+    // 本地 HTTP 服务, 按路径返回固定的响应
+    fn serve() -> Server {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Arc::new(AtomicUsize::new(0));
+        let counter = requests.clone();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let local = format!("http://127.0.0.1:{}/plain", port);
+                let (status, headers, body): (&str, String, Vec<u8>) = match path {
+                    "/" => (
+                        "200 OK",
+                        "Content-Type: text/html; charset=utf-8".to_string(),
+                        br#"<html><head><title> Hello &amp;
+                            World </title><meta property="og:description" content="Desc here">
+                            <link rel="shortcut icon" href="/icon.png"></head></html>"#
+                            .to_vec(),
+                    ),
+                    "/plain" => (
+                        "200 OK",
+                        "Content-Type: text/html".to_string(),
+                        b"<title>Plain</title>".to_vec(),
+                    ),
+                    "/private-icon" => (
+                        "200 OK",
+                        "Content-Type: text/html".to_string(),
+                        br#"<title>Icon</title><link rel="icon" href="http://192.168.1.1/icon.png">"#
+                            .to_vec(),
+                    ),
+                    "/favicon.ico" => (
+                        "200 OK",
+                        "Content-Type: image/x-icon".to_string(),
+                        vec![0, 0, 1, 0, 1, 0],
+                    ),
+                    "/icon.png" => ("200 OK", "Content-Type: image/png".to_string(), PNG.to_vec()),
+                    "/text" => ("200 OK", "Content-Type: text/plain".to_string(), b"text".to_vec()),
+                    "/redirect-local" => ("302 Found", format!("Location: {}", local), Vec::new()),
+                    "/redirect-private" => (
+                        "302 Found",
+                        "Location: http://10.0.0.1/".to_string(),
+                        Vec::new(),
+                    ),
+                    "/redirect-metadata" => (
+                        "302 Found",
+                        "Location: http://169.254.169.254/latest/meta-data/".to_string(),
+                        Vec::new(),
+                    ),
+                    "/redirect-blocked" => (
+                        "302 Found",
+                        "Location: http://blocked.example/".to_string(),
+                        Vec::new(),
+                    ),
+                    _ => ("404 Not Found", "Content-Type: text/plain".to_string(), Vec::new()),
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\n{}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    headers,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+        Server { port, requests }
+    }
+
+    fn blob_store() -> (tempfile::TempDir, BlobStore) {
+        let dir = tempfile::tempdir().unwrap();
+        let blobs = BlobStore::open(dir.path()).unwrap();
+        (dir, blobs)
+    }
+
+    fn url(text: &str) -> Url {
+        Url::parse(text).unwrap()
+    }
+
+    #[test]
+    fn parse_html_prefers_og_tags() {
+        let page = parse_html(
+            r#"<head>
+                <title>Page title</title>
+                <meta name="description" content="Plain description">
+                <META property='og:title' content='OG &quot;title&quot;'>
+                <meta content="OG description" property="og:description">
+                <link rel="stylesheet" href="/style.css">
+                <link rel="apple-touch-icon icon" href=/icon.png>
+            </head>"#,
+        );
+        assert_eq!(page.title.as_deref(), Some("OG \"title\""));
+        assert_eq!(page.description.as_deref(), Some("OG description"));
+        assert_eq!(page.icon_href.as_deref(), Some("/icon.png"));
+
+        let page = parse_html(
+            "<title>\n  A &amp;\n B  </title><meta name=\"description\" content=\"Desc\">",
+        );
+        assert_eq!(page.title.as_deref(), Some("A & B"));
+        assert_eq!(page.description.as_deref(), Some("Desc"));
+        assert_eq!(page.icon_href, None);
+
+        let long = format!("<title>{}</title>", "a".repeat(1000));
+        assert_eq!(
+            parse_html(&long).title.map(|t| t.chars().count()),
+            Some(MAX_TEXT_CHARS)
+        );
+        let page = parse_html("<title> </title><link rel=\"icon\" href=\"\">");
+        assert_eq!(page.title, None);
+        assert_eq!(page.icon_href, None);
+    }
+
+    #[test]
+    fn blocked_domains_match_subdomains() {
+        let blocked = vec![".Example.com".to_string(), " ".to_string()];
+        assert!(is_blocked(&url("https://example.com/a"), &blocked));
+        assert!(is_blocked(&url("https://docs.EXAMPLE.com/"), &blocked));
+        assert!(!is_blocked(&url("https://notexample.com/"), &blocked));
+        assert!(!is_blocked(&url("https://example.com.cn/"), &blocked));
+        assert!(!is_blocked(&url("https://example.org/"), &[]));
+    }
+
+    #[test]
+    fn private_addresses_are_not_allowed() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "::1",
+            "::",
+            "fc00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:10.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+        for ip in ["93.184.216.34", "1.1.1.1", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{}", ip);
+        }
+
+        assert!(is_allowed_ip("127.0.0.1".parse().unwrap(), true));
+        assert!(!is_allowed_ip("10.0.0.1".parse().unwrap(), true));
+        assert!(!is_allowed_host(&url("http://[::1]:8080/"), false));
+        assert!(!is_allowed_host(&url("http://2130706433/"), false));
+        assert!(is_allowed_host(&url("http://example.com/"), false));
+    }
+
+    #[test]
+    fn fetch_preview_refuses_local_services() {
+        let (_dir, blobs) = blob_store();
+        let server = serve();
+
+        assert!(fetch_preview(&blobs, &server.url("/"), &[])
+            .unwrap()
+            .is_none());
+        let ipv6 = format!("http://[::1]:{}/", server.port);
+        assert!(fetch_preview(&blobs, &ipv6, &[]).unwrap().is_none());
+        // 域名解析到本机地址时同样拒绝
+        let localhost = format!("http://localhost:{}/", server.port);
+        assert!(fetch_preview(&blobs, &localhost, &[]).is_err());
+        assert_eq!(server.requests.load(Ordering::SeqCst), 0);
+    }
+
+    #[test]
+    fn fetch_reads_title_and_favicon() {
+        let (_dir, blobs) = blob_store();
+        let server = serve();
+
+        let preview = fetch(&blobs, &server.url("/"), &[], true).unwrap().unwrap();
+        assert_eq!(preview.url, server.url("/"));
+        assert_eq!(preview.title.as_deref(), Some("Hello & World"));
+        assert_eq!(preview.description.as_deref(), Some("Desc here"));
+        assert!(preview.favicon_key.unwrap().ends_with(".png"));
+
+        // 没有声明图标时使用 /favicon.ico
+        let preview = fetch(&blobs, &server.url("/plain"), &[], true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Plain"));
+        assert!(preview.favicon_key.unwrap().ends_with(".ico"));
+
+        // 图标指向内网地址时不抓取图标
+        let preview = fetch(&blobs, &server.url("/private-icon"), &[], true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Icon"));
+        assert_eq!(preview.favicon_key, None);
+
+        assert!(fetch(&blobs, &server.url("/missing"), &[], true).is_err());
+        assert!(fetch(&blobs, &server.url("/text"), &[], true).is_err());
+        assert!(fetch(&blobs, "ftp://example.com", &[], true).is_err());
+        let blocked = vec!["127.0.0.1".to_string()];
+        assert!(fetch(&blobs, &server.url("/"), &blocked, true)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn redirects_are_checked() {
+        let (_dir, blobs) = blob_store();
+        let server = serve();
+
+        let preview = fetch(&blobs, &server.url("/redirect-local"), &[], true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(preview.title.as_deref(), Some("Plain"));
+
+        assert!(fetch(&blobs, &server.url("/redirect-private"), &[], true).is_err());
+        assert!(fetch(&blobs, &server.url("/redirect-metadata"), &[], true).is_err());
+        let blocked = vec!["blocked.example".to_string()];
+        assert!(
+            fetch(&blobs, &server.url("/redirect-blocked"), &blocked, true)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
@@ -0,0 +1,85 @@
+use lazy_static::lazy_static;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use tauri::Manager;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, reload, EnvFilter, Registry};
+
+use crate::utils::error::{Error, Result};
+use crate::utils::settings::{self, LogLevel};
+
+// 日志按天切分, 保存在 app 日志目录下, 只保留最近几天
+const LOG_FILE_PREFIX: &str = "clippy2";
+const LOG_FILE_SUFFIX: &str = "log";
+const MAX_LOG_FILES: usize = 7;
+
+lazy_static! {
+    static ref FILTER_HANDLE: Mutex<Option<reload::Handle<EnvFilter, Registry>>> =
+        Mutex::new(None);
+    static ref LOG_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+    // 日志由后台线程写入文件, guard 释放时才会刷新剩余的日志, 需要一直持有
+    static ref LOG_GUARD: Mutex<Option<WorkerGuard>> = Mutex::new(None);
+}
+
+pub fn init(app: &tauri::App) -> Result<()> {
+    let log_dir = app.app_handle().path().app_log_dir()?;
+    std::fs::create_dir_all(&log_dir)?;
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_FILE_PREFIX)
+        .filename_suffix(LOG_FILE_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(&log_dir)
+        .map_err(|e| Error::Other(format!("Failed to create log file: {}", e)))?;
+    let (file_writer, guard) = tracing_appender::non_blocking(appender);
+
+    // 日志级别可在运行时修改
+    let (filter, handle) = reload::Layer::new(build_filter(settings::get().log_level));
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(file_writer).with_ansi(false))
+        // 只在开发时同时输出到终端, 打包后的应用没有终端
+        .with(cfg!(debug_assertions).then(|| fmt::layer().with_writer(std::io::stdout)))
+        .try_init()
+        .map_err(|e| Error::Other(format!("Failed to init logger: {}", e)))?;
+
+    *FILTER_HANDLE.lock().unwrap() = Some(handle);
+    *LOG_DIR.lock().unwrap() = Some(log_dir);
+    *LOG_GUARD.lock().unwrap() = Some(guard);
+
+    // panic 默认只输出到 stderr, 打包后看不到, 同时记录到日志中
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!(panic = %info, "thread panicked");
+        default_hook(info);
+    }));
+
+    Ok(())
+}
+
+// 只按设置的级别输出本应用的日志, 依赖库的日志只保留 warn 以上
+fn build_filter(level: LogLevel) -> EnvFilter {
+    EnvFilter::new(format!(
+        "warn,{}={}",
+        env!("CARGO_CRATE_NAME"),
+        level.as_str()
+    ))
+}
+
+pub fn set_level(level: LogLevel) {
+    let Some(handle) = FILTER_HANDLE.lock().unwrap().clone() else {
+        return;
+    };
+    match handle.reload(build_filter(level)) {
+        Ok(_) => tracing::info!(level = level.as_str(), "log level changed"),
+        Err(e) => tracing::error!(error = %e, "failed to change log level"),
+    }
+}
+
+pub fn log_dir() -> Option<PathBuf> {
+    LOG_DIR.lock().unwrap().clone()
+}
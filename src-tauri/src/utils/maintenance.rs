@@ -0,0 +1,451 @@
+use rusqlite::{Connection, OpenFlags};
+use serde::Serialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::State;
+
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+
+// 数据库的备份、完整性检查、损坏恢复和空间回收
+// 备份保存在 `backups/app-<unix 时间戳>.db`, 按时间戳排序
+
+// 保留的备份数量和备份间隔
+const BACKUP_COUNT: usize = 3;
+const BACKUP_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+// 后台检查是否需要备份和 VACUUM 的间隔
+const CHECK_INTERVAL: Duration = Duration::from_secs(10 * 60);
+// 空闲空间超过 32MB, 或超过 1MB 且占数据库的 1/4 时执行 VACUUM, 通常发生在大量清理之后
+const VACUUM_MIN_FREE_BYTES: i64 = 32 * 1024 * 1024;
+const VACUUM_MIN_FREE_RATIO_BYTES: i64 = 1024 * 1024;
+// 维护连接等待写锁的时间, 超时后放弃本次维护
+const BUSY_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn backup_dir(db_path: &Path) -> PathBuf {
+    db_path.with_file_name("backups")
+}
+
+// 定时备份, 清理出较多空闲空间后回收
+pub fn init(db: Database) {
+    let dir = backup_dir(db.path());
+
+    std::thread::spawn(move || loop {
+        if let Err(e) = backup_if_due(&db, &dir) {
+            tracing::error!(error = %e, "failed to back up database");
+        }
+        match vacuum_if_needed(&db) {
+            Ok(Some(reclaimed)) => tracing::info!(reclaimed, "database vacuumed"),
+            Ok(None) => {}
+            Err(e) => tracing::error!(error = %e, "failed to vacuum database"),
+        }
+        std::thread::sleep(CHECK_INTERVAL);
+    });
+}
+
+// 打开数据库前检查完整性, 损坏时先从最近的可用备份恢复, 没有可用备份时通过 .recover 抢救数据,
+// 都失败时由调用方创建新的数据库, 损坏的文件保留为 app.db.corrupt-<时间戳> 便于排查
+pub fn check_and_recover(db_path: &Path) -> Result<()> {
+    if !db_path.exists() {
+        return Ok(());
+    }
+    let e = match check_file(db_path) {
+        Ok(()) => return Ok(()),
+        Err(e) => e,
+    };
+    tracing::error!(error = %e, "database is corrupted, recovering");
+
+    let corrupt_path = move_aside(db_path)?;
+    if let Some(backup) = restore_backup(db_path, &backup_dir(db_path))? {
+        tracing::warn!(backup = %backup.display(), "database restored from backup");
+        return Ok(());
+    }
+    match recover(&corrupt_path, db_path) {
+        Ok(()) => tracing::warn!("database recovered with .recover"),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to recover database, starting with an empty one");
+            remove_db_files(db_path);
+        }
+    }
+    Ok(())
+}
+
+// quick_check 不检查索引和表内容是否一致, 比 integrity_check 快很多
+fn quick_check(conn: &Connection) -> Result<(), rusqlite::Error> {
+    let mut stmt = conn.prepare("PRAGMA quick_check")?;
+    let messages = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    if messages == ["ok"] {
+        return Ok(());
+    }
+    Err(rusqlite::Error::SqliteFailure(
+        rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_CORRUPT),
+        Some(messages.join("; ")),
+    ))
+}
+
+fn check_file(path: &Path) -> Result<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    Ok(quick_check(&conn)?)
+}
+
+// 备份和 VACUUM 使用单独的读写连接, 不占用应用的写连接;
+// 应用正在写入时通过 busy_timeout 等待, 而不是让应用的写操作等待维护结束
+fn connect(db: &Database) -> Result<Connection> {
+    let conn = Connection::open_with_flags(db.path(), OpenFlags::SQLITE_OPEN_READ_WRITE)?;
+    conn.busy_timeout(BUSY_TIMEOUT)?;
+    Ok(conn)
+}
+
+fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+// WAL 模式下未合并的数据在 -wal 文件中, 和数据库文件一起移走
+fn db_files(db_path: &Path) -> [PathBuf; 3] {
+    let path = db_path.to_string_lossy();
+    [
+        db_path.to_path_buf(),
+        PathBuf::from(format!("{}-wal", path)),
+        PathBuf::from(format!("{}-shm", path)),
+    ]
+}
+
+fn move_aside(db_path: &Path) -> Result<PathBuf> {
+    let corrupt_path = PathBuf::from(format!(
+        "{}.corrupt-{}",
+        db_path.to_string_lossy(),
+        unix_time()
+    ));
+    for (from, to) in db_files(db_path).iter().zip(db_files(&corrupt_path)) {
+        if from.exists() {
+            std::fs::rename(from, to)?;
+        }
+    }
+    Ok(corrupt_path)
+}
+
+fn remove_db_files(db_path: &Path) {
+    for path in db_files(db_path) {
+        let _ = std::fs::remove_file(path);
+    }
+}
+
+// 按时间从新到旧排列的备份
+fn list_backups(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut backups: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.file_name()
+                .and_then(|name| name.to_str())
+                .is_some_and(|name| name.starts_with("app-") && name.ends_with(".db"))
+        })
+        .collect();
+    backups.sort();
+    backups.reverse();
+    backups
+}
+
+fn restore_backup(db_path: &Path, dir: &Path) -> Result<Option<PathBuf>> {
+    for backup in list_backups(dir) {
+        if let Err(e) = check_file(&backup) {
+            tracing::warn!(backup = %backup.display(), error = %e, "skipping corrupted backup");
+            continue;
+        }
+        std::fs::copy(&backup, db_path)?;
+        return Ok(Some(backup));
+    }
+    Ok(None)
+}
+
+// 通过 sqlite3 命令行的 .recover 导出能读出的数据, 再导入新的数据库
+// 导出的内容包含 .dbconfig 等命令, 同样交给 sqlite3 执行
+fn recover(corrupt_path: &Path, db_path: &Path) -> Result<()> {
+    let dump = Command::new("sqlite3")
+        .arg(corrupt_path)
+        .arg(".recover")
+        .output()
+        .map_err(sqlite3_error)?;
+    if !dump.status.success() {
+        return Err(Error::Other(format!(
+            "sqlite3 .recover failed: {}",
+            String::from_utf8_lossy(&dump.stderr)
+        )));
+    }
+
+    remove_db_files(db_path);
+    let mut import = Command::new("sqlite3")
+        .arg("-bail")
+        .arg(db_path)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(sqlite3_error)?;
+    if let Some(mut stdin) = import.stdin.take() {
+        stdin.write_all(&dump.stdout)?;
+    }
+    let import = import.wait_with_output()?;
+    if !import.status.success() {
+        return Err(Error::Other(format!(
+            "Failed to import recovered data: {}",
+            String::from_utf8_lossy(&import.stderr)
+        )));
+    }
+    check_file(db_path)
+}
+
+// 系统中没有 sqlite3 时无法 .recover, 单独说明原因
+fn sqlite3_error(e: std::io::Error) -> Error {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        Error::Other("sqlite3 is not installed, cannot run .recover".to_string())
+    } else {
+        Error::Other(format!("Failed to run sqlite3: {}", e))
+    }
+}
+
+// 写入新的备份并删除多余的旧备份, 返回备份文件路径
+pub fn backup(db: &Database, dir: &Path) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("app-{}.db", unix_time()));
+    // VACUUM INTO 在一个读事务中生成一致的快照, 不阻塞应用的写入
+    connect(db)?.execute("VACUUM INTO ?1", [path.to_string_lossy().into_owned()])?;
+    if let Err(e) = check_file(&path) {
+        let _ = std::fs::remove_file(&path);
+        return Err(e);
+    }
+
+    for old in list_backups(dir).into_iter().skip(BACKUP_COUNT) {
+        let _ = std::fs::remove_file(old);
+    }
+    Ok(path)
+}
+
+fn backup_if_due(db: &Database, dir: &Path) -> Result<()> {
+    let latest = list_backups(dir)
+        .first()
+        .and_then(|path| std::fs::metadata(path).ok())
+        .and_then(|m| m.modified().ok())
+        .and_then(|t| SystemTime::now().duration_since(t).ok());
+    if matches!(latest, Some(age) if age < BACKUP_INTERVAL) {
+        return Ok(());
+    }
+    let path = backup(db, dir)?;
+    tracing::info!(path = %path.display(), "database backed up");
+    Ok(())
+}
+
+// 数据库文件的大小(不含 -wal), 单位 bytes
+fn db_size(conn: &Connection) -> Result<i64, rusqlite::Error> {
+    conn.query_row(
+        "SELECT page_count * page_size FROM pragma_page_count(), pragma_page_size()",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// VACUUM 后更新统计信息, 并把 -wal 中的数据合并回数据库文件, 返回维护前后数据库文件的大小
+fn vacuum(db: &Database) -> Result<(i64, i64)> {
+    let conn = connect(db)?;
+    let size_before = db_size(&conn)?;
+    conn.execute_batch("VACUUM; ANALYZE;")?;
+    // 有读连接未结束时 checkpoint 不能完成, 返回 busy 而不是报错, 留给下次 checkpoint
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))?;
+    Ok((size_before, db_size(&conn)?))
+}
+
+fn vacuum_if_needed(db: &Database) -> Result<Option<i64>> {
+    let (free_pages, page_count, page_size): (i64, i64, i64) = db.read(|conn| {
+        conn.query_row(
+            "SELECT freelist_count, page_count, page_size
+             FROM pragma_freelist_count(), pragma_page_count(), pragma_page_size()",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )
+    })?;
+    let free_bytes = free_pages * page_size;
+    let needed = free_bytes >= VACUUM_MIN_FREE_BYTES
+        || (free_bytes >= VACUUM_MIN_FREE_RATIO_BYTES && free_pages * 4 >= page_count);
+    if !needed {
+        return Ok(None);
+    }
+    let (size_before, size_after) = vacuum(db)?;
+    Ok(Some(size_before - size_after))
+}
+
+#[derive(Debug, Serialize)]
+pub struct MaintenanceReport {
+    pub integrity_ok: bool,
+    // 完整性检查的错误信息
+    pub integrity_error: Option<String>,
+    // 维护前后数据库文件的大小和回收的空间, 单位 bytes
+    pub size_before: i64,
+    pub size_after: i64,
+    pub reclaimed_bytes: i64,
+    pub backup_count: usize,
+}
+
+// 检查完整性, 没有问题时执行 VACUUM 和 ANALYZE
+pub fn run(db: &Database) -> Result<MaintenanceReport> {
+    let (size, integrity) = db.read(|conn| Ok((db_size(conn)?, quick_check(conn))))?;
+    let integrity_error = integrity.err().map(|e| e.to_string());
+    let (size_before, size_after) = match integrity_error {
+        None => vacuum(db)?,
+        Some(_) => (size, size),
+    };
+    let backup_count = list_backups(&backup_dir(db.path())).len();
+
+    Ok(MaintenanceReport {
+        integrity_ok: integrity_error.is_none(),
+        integrity_error,
+        size_before,
+        size_after,
+        reclaimed_bytes: size_before - size_after,
+        backup_count,
+    })
+}
+
+#[tauri::command]
+pub async fn run_maintenance(db: State<'_, Database>) -> Result<MaintenanceReport> {
+    let report = db.run(run).await?;
+    tracing::info!(?report, "database maintenance finished");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::{self, RecordInput};
+
+    fn open() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        (dir, db)
+    }
+
+    fn values(db_path: &Path) -> Vec<String> {
+        let conn = Connection::open(db_path).unwrap();
+        let mut stmt = conn
+            .prepare("SELECT value FROM record ORDER BY id")
+            .unwrap();
+        let values = stmt
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        values
+    }
+
+    // 保留文件头, 覆盖后面的页, 文件能打开但 quick_check 失败
+    fn corrupt(db: Database) -> PathBuf {
+        let path = db.path().to_path_buf();
+        drop(db);
+        let mut bytes = std::fs::read(&path).unwrap();
+        let page_size = u16::from_be_bytes([bytes[16], bytes[17]]) as usize;
+        bytes[page_size..].fill(0xff);
+        std::fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    #[test]
+    fn backup_keeps_the_newest_copies() {
+        let (_dir, db) = open();
+        db.add_record(RecordInput::text("backed up")).unwrap();
+        let dir = backup_dir(db.path());
+        std::fs::create_dir_all(&dir).unwrap();
+        for time in 1000000001..=1000000004 {
+            std::fs::write(dir.join(format!("app-{}.db", time)), "").unwrap();
+        }
+        std::fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let path = backup(&db, &dir).unwrap();
+        assert_eq!(
+            list_backups(&dir),
+            vec![
+                path.clone(),
+                dir.join("app-1000000004.db"),
+                dir.join("app-1000000003.db"),
+            ]
+        );
+        assert!(dir.join("notes.txt").exists());
+        assert_eq!(values(&path), vec!["backed up".to_string()]);
+    }
+
+    #[test]
+    fn restore_skips_corrupted_backups() {
+        let (dir, db) = open();
+        db.add_record(RecordInput::text("backed up")).unwrap();
+        let backups = backup_dir(db.path());
+        let path = backup(&db, &backups).unwrap();
+        std::fs::write(backups.join("app-9999999999.db"), "not a database").unwrap();
+
+        let target = dir.path().join("restored.db");
+        assert_eq!(restore_backup(&target, &backups).unwrap(), Some(path));
+        assert_eq!(values(&target), vec!["backed up".to_string()]);
+
+        let empty = dir.path().join("empty");
+        assert_eq!(restore_backup(&target, &empty).unwrap(), None);
+    }
+
+    #[test]
+    fn corrupted_database_is_restored_from_backup() {
+        let (dir, db) = open();
+        db.add_record(RecordInput::text("backed up")).unwrap();
+        backup(&db, &backup_dir(db.path())).unwrap();
+        db.add_record(RecordInput::text("after backup")).unwrap();
+        let path = corrupt(db);
+        assert!(check_file(&path).is_err());
+
+        check_and_recover(&path).unwrap();
+        assert!(check_file(&path).is_ok());
+        assert_eq!(values(&path), vec!["backed up".to_string()]);
+        // 损坏的文件保留下来
+        let moved = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .any(|entry| entry.file_name().to_string_lossy().contains(".corrupt-"));
+        assert!(moved);
+        db::init_at(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn corrupted_database_without_backup_is_replaced() {
+        let (dir, db) = open();
+        db.add_record(RecordInput::text("lost")).unwrap();
+        let path = corrupt(db);
+
+        // 有 sqlite3 时尽量抢救, 没有时重新建库, 两种情况都能正常打开
+        check_and_recover(&path).unwrap();
+        assert!(!path.exists() || check_file(&path).is_ok());
+        db::init_at(dir.path()).unwrap();
+    }
+
+    #[test]
+    fn run_reports_the_measured_size() {
+        let (_dir, db) = open();
+        for i in 0..20 {
+            db.add_record(RecordInput::text(&format!("{}{}", i, "x".repeat(100_000))))
+                .unwrap();
+        }
+        db.write(|conn| conn.execute("DELETE FROM record", []))
+            .unwrap();
+
+        let report = run(&db).unwrap();
+        assert!(report.integrity_ok);
+        assert!(report.size_after < report.size_before);
+        assert_eq!(
+            report.reclaimed_bytes,
+            report.size_before - report.size_after
+        );
+        assert_eq!(report.size_after, db.read(db_size).unwrap());
+    }
+}
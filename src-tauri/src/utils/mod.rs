@@ -1,8 +1,26 @@
+pub mod api;
+pub mod blob_store;
+pub mod classify;
 pub mod clipboard_read;
 pub mod clipboard_write;
 pub mod db;
+pub mod diagnostics;
+pub mod edit_img;
+pub mod error;
+pub mod expiry;
 pub mod global_shortcut;
+pub mod highlight;
+pub mod img_worker;
+pub mod link_preview;
+pub mod logger;
+pub mod maintenance;
 pub mod monitor;
 pub mod nspanel;
+pub mod ocr;
 pub mod optimize_img;
+pub mod pause;
+pub mod settings;
+pub mod statistics;
+pub mod sync;
+pub mod tasks;
 pub mod tray;
@@ -1,3 +1,4 @@
+use crate::utils::error::{Error, Result};
 use crate::utils::monitor;
 use cocoa::appkit::{NSMainMenuWindowLevel, NSWindowCollectionBehavior};
 use lazy_static::lazy_static;
@@ -45,11 +46,11 @@ pub fn init(app: &tauri::App) {
     delegate.set_listener(Box::new(move |delegate_name: String| {
         match delegate_name.as_str() {
             "window_did_become_key" => {
-                println!("[info]: panel becomes key window!");
+                tracing::debug!("panel becomes key window");
                 PANEL_STATE.lock().unwrap().show();
             }
             "window_did_resign_key" => {
-                println!("[info]: panel resigned from key window!");
+                tracing::debug!("panel resigned from key window");
                 panel_ref.order_out(None);
                 PANEL_STATE.lock().unwrap().hide();
             }
@@ -76,33 +77,35 @@ pub fn init(app: &tauri::App) {
 const WIN_HEIGHT: f64 = 322.0;
 
 #[tauri::command]
-pub fn toggle_panel(handle: AppHandle) {
-    let panel = handle.get_webview_panel("main").unwrap();
-    let win: WebviewWindow = handle.get_webview_window("main").unwrap();
+pub fn toggle_panel(handle: AppHandle) -> Result<()> {
+    let panel = handle
+        .get_webview_panel("main")
+        .map_err(|_| Error::Other("Panel not found".to_string()))?;
+    let win: WebviewWindow = handle
+        .get_webview_window("main")
+        .ok_or_else(|| Error::Other("Window not found".to_string()))?;
 
     if panel.is_visible() {
         panel.order_out(None);
         // 隐藏时将win的y坐标设置到tauri.conf.json中设置的y坐标, 窗口从一个屏幕转到另一个屏幕时, 窗口不会闪烁
-        win.set_position(LogicalPosition::new(0.0, -100000.0))
-            .unwrap();
-        return;
+        win.set_position(LogicalPosition::new(0.0, -100000.0))?;
+        return Ok(());
     }
 
     let monitor = monitor::get_active_monitor(&handle);
     let size = monitor.size().to_logical(monitor.scale_factor());
     let position = monitor.position();
 
-    win.set_size(LogicalSize::new(size.width, WIN_HEIGHT))
-        .unwrap();
+    win.set_size(LogicalSize::new(size.width, WIN_HEIGHT))?;
     win.set_position(LogicalPosition::new(
         position.x as f64,
         size.height - WIN_HEIGHT + position.y as f64,
-    ))
-    .unwrap();
+    ))?;
 
     panel.show();
     // 只有focus状态下才能触发window_did_resign_key
-    win.set_focus().unwrap();
+    win.set_focus()?;
+    Ok(())
 }
 
 // #[tauri::command]
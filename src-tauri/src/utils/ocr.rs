@@ -0,0 +1,263 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use cocoa::base::{id, nil};
+use cocoa::foundation::NSString;
+use objc::runtime::{Class, YES};
+use objc::{class, msg_send, sel, sel_impl};
+use rusqlite::OptionalExtension;
+use std::sync::mpsc::{self, Sender};
+
+use crate::utils::clipboard_read::nsstring_to_rust_string;
+use crate::utils::db::Database;
+use crate::utils::error::{Error, Result};
+use crate::utils::settings;
+
+// 使用系统自带的 Vision 框架在本地识别图片中的文字
+#[link(name = "Vision", kind = "framework")]
+extern "C" {}
+
+// 启动识别线程, 返回识别队列, 由 Tasks 持有
+pub fn init(db: Database) -> Sender<i64> {
+    let (sender, receiver) = mpsc::channel::<i64>();
+
+    // 在单独的线程中排队识别, 不阻塞剪贴板轮询
+    std::thread::spawn(move || {
+        // 补齐之前没有识别过的图片
+        if settings::get().ocr.enabled {
+            for id in pending(&db) {
+                process(&db, id);
+            }
+        }
+
+        for id in receiver {
+            process(&db, id);
+        }
+    });
+    sender
+}
+
+// 还没有识别过的图片, 最近使用的优先
+fn pending(db: &Database) -> Vec<i64> {
+    db.read(|conn| {
+        let mut stmt = conn.prepare(
+            "SELECT id FROM record
+             WHERE record_type = 'image' AND ocr_text IS NULL AND deleted_at IS NULL
+             ORDER BY updated_at DESC",
+        )?;
+        let ids = stmt
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(ids)
+    })
+    .unwrap_or_default()
+}
+
+fn process(db: &Database, id: i64) {
+    let ocr_settings = settings::get().ocr;
+    if !ocr_settings.enabled {
+        return;
+    }
+    process_with(db, id, &ocr_settings.languages, recognize_text);
+}
+
+// 识别函数作为参数传入, 测试时不需要调用 Vision
+fn process_with(
+    db: &Database,
+    id: i64,
+    languages: &[String],
+    recognize: fn(&[u8], &[String]) -> Result<String>,
+) {
+    let value: Option<String> = db
+        .read(|conn| {
+            conn.query_row(
+                "SELECT value FROM record
+                 WHERE id = ?1 AND record_type = 'image' AND ocr_text IS NULL",
+                [id],
+                |row| row.get(0),
+            )
+            .optional()
+        })
+        .unwrap_or(None);
+
+    let Some(value) = value else {
+        return;
+    };
+
+    // 识别失败时记为空字符串, 避免反复重试
+    let text = STANDARD
+        .decode(value)
+        .map_err(Error::from)
+        .and_then(|bytes| recognize(&bytes, languages))
+        .unwrap_or_else(|e| {
+            tracing::error!(id, error = %e, "failed to recognize text");
+            String::new()
+        });
+
+    let _ =
+        db.write(|conn| conn.execute("UPDATE record SET ocr_text = ?1 WHERE id = ?2", (&text, id)));
+}
+
+fn recognize_text(img_bytes: &[u8], languages: &[String]) -> Result<String> {
+    // VNRecognizeTextRequest 需要 macOS 10.15 及以上
+    let Some(request_class) = Class::get("VNRecognizeTextRequest") else {
+        return Err(Error::Unsupported(
+            "Text recognition is not supported on this system".to_string(),
+        ));
+    };
+
+    unsafe {
+        objc::rc::autoreleasepool(|| {
+            let bytes = img_bytes.as_ptr() as *const std::os::raw::c_void;
+            let data: id = msg_send![class!(NSData), dataWithBytes:bytes length:img_bytes.len()];
+            let options: id = msg_send![class!(NSDictionary), dictionary];
+
+            let handler: id = msg_send![class!(VNImageRequestHandler), alloc];
+            let handler: id = msg_send![handler, initWithData:data options:options];
+            let request: id = msg_send![request_class, alloc];
+            let request: id = msg_send![request, init];
+
+            // VNRequestTextRecognitionLevelAccurate
+            let _: () = msg_send![request, setRecognitionLevel:0isize];
+            let _: () = msg_send![request, setUsesLanguageCorrection:YES];
+            if !languages.is_empty() {
+                let langs: id = msg_send![class!(NSMutableArray), array];
+                for lang in languages {
+                    let lang = NSString::alloc(nil).init_str(lang);
+                    let _: () = msg_send![langs, addObject:lang];
+                }
+                let _: () = msg_send![request, setRecognitionLanguages:langs];
+            }
+
+            let requests: id = msg_send![class!(NSArray), arrayWithObject:request];
+            let mut error: id = nil;
+            let success: bool = msg_send![handler, performRequests:requests error:&mut error];
+
+            let result = if success {
+                let results: id = msg_send![request, results];
+                let count: usize = msg_send![results, count];
+                let mut lines = Vec::new();
+                for i in 0..count {
+                    let observation: id = msg_send![results, objectAtIndex:i];
+                    let candidates: id = msg_send![observation, topCandidates:1usize];
+                    let candidate: id = msg_send![candidates, firstObject];
+                    if candidate != nil {
+                        let string: id = msg_send![candidate, string];
+                        lines.push(nsstring_to_rust_string(string));
+                    }
+                }
+                Ok(lines.join("\n"))
+            } else {
+                Err(Error::Other(
+                    "Failed to perform text recognition".to_string(),
+                ))
+            };
+
+            let _: () = msg_send![request, release];
+            let _: () = msg_send![handler, release];
+            result
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::{self, RecordInput};
+    use std::io::Cursor;
+
+    // 2x2 的 png 图片
+    fn fixture_png() -> String {
+        let img = image::RgbaImage::from_pixel(2, 2, image::Rgba([255, 255, 255, 255]));
+        let mut bytes = Vec::new();
+        img.write_to(&mut Cursor::new(&mut bytes), image::ImageFormat::Png)
+            .unwrap();
+        STANDARD.encode(bytes)
+    }
+
+    fn ocr_text(db: &Database, id: i64) -> Option<String> {
+        db.read(|conn| {
+            conn.query_row("SELECT ocr_text FROM record WHERE id = ?1", [id], |row| {
+                row.get(0)
+            })
+        })
+        .unwrap()
+    }
+
+    fn recognized(bytes: &[u8], languages: &[String]) -> Result<String> {
+        assert_eq!(image::guess_format(bytes).unwrap(), image::ImageFormat::Png);
+        Ok(format!("text in {}", languages.join(",")))
+    }
+
+    fn failed(_bytes: &[u8], _languages: &[String]) -> Result<String> {
+        Err(Error::Other("recognition failed".to_string()))
+    }
+
+    #[test]
+    fn recognized_text_is_saved() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        let languages = vec!["en-US".to_string()];
+
+        let id = db
+            .add_record(RecordInput::text(&fixture_png()).with_type("image"))
+            .unwrap();
+        assert_eq!(pending(&db), vec![id]);
+        process_with(&db, id, &languages, recognized);
+        assert_eq!(ocr_text(&db, id).as_deref(), Some("text in en-US"));
+        assert!(pending(&db).is_empty());
+
+        // 已经识别过的图片不会重复识别
+        process_with(&db, id, &languages, failed);
+        assert_eq!(ocr_text(&db, id).as_deref(), Some("text in en-US"));
+    }
+
+    #[test]
+    fn failures_are_saved_as_empty_text() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+
+        let id = db
+            .add_record(RecordInput::text(&fixture_png()).with_type("image"))
+            .unwrap();
+        process_with(&db, id, &[], failed);
+        assert_eq!(ocr_text(&db, id).as_deref(), Some(""));
+
+        // 无法解码的数据同样记为空字符串, 之后不再重试
+        let invalid = db
+            .add_record(RecordInput::text("not base64!").with_type("image"))
+            .unwrap();
+        process_with(&db, invalid, &[], recognized);
+        assert_eq!(ocr_text(&db, invalid).as_deref(), Some(""));
+        assert!(pending(&db).is_empty());
+    }
+
+    // 调用系统的 Vision 识别截图中的文字, 只能在 macOS 上运行
+    #[cfg(target_os = "macos")]
+    #[test]
+    fn vision_recognizes_fixture_screenshot() {
+        let bytes = include_bytes!("../../fixtures/ocr-hello.png");
+        let text = recognize_text(bytes, &["en-US".to_string()]).unwrap();
+        let text = text.to_uppercase();
+        assert!(
+            text.contains("HELLO") && text.contains("CLIPPY"),
+            "{}",
+            text
+        );
+    }
+
+    #[test]
+    fn only_images_are_processed() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+
+        let text = db.add_record(RecordInput::text("hello")).unwrap();
+        let deleted = db
+            .add_record(RecordInput::text(&fixture_png()).with_type("image"))
+            .unwrap();
+        db.soft_delete(&[deleted], false).unwrap();
+        assert!(pending(&db).is_empty());
+
+        process_with(&db, text, &[], recognized);
+        assert_eq!(ocr_text(&db, text), None);
+    }
+}
@@ -1,16 +1,114 @@
-use base64::engine::general_purpose::STANDARD;
-use base64::Engine;
-use image::{self, GenericImageView};
+use image::codecs::avif::AvifEncoder;
+use image::codecs::webp::WebPEncoder;
+use image::{self, GenericImageView, ImageDecoder, ImageReader, Limits, RgbaImage};
 use imagequant::Attributes;
 use lodepng;
+use std::io::Cursor;
 
-// 图片压缩
-pub fn optimize_img(img_bytes: &[u8]) -> Result<(String, String), String> {
-    let img: image::DynamicImage =
-        image::load_from_memory(&img_bytes).map_err(|e| e.to_string())?;
-    let (resized_img, img_size, (new_width, new_height)) = resize_img(img);
+use crate::utils::blob_store::BlobStore;
+use crate::utils::error::{Error, Result};
+use crate::utils::settings::{self, ThumbnailFormat};
 
-    let pixels: Vec<imagequant::RGBA> = resized_img
+pub struct OptimizedImg {
+    // 图片尺寸 宽x高
+    pub img_size: String,
+    // 感知哈希(dHash), 缩放或格式不同的同一张图哈希相近
+    pub phash: u64,
+    // 列表中显示的缩略图, 存放在 blob store 中
+    pub thumbnail_key: String,
+    // 鼠标悬停时显示的大图
+    pub preview_key: String,
+}
+
+// 列表缩略图的最长边
+const THUMBNAIL_EDGE_SIZE: usize = 240;
+// 悬停预览图的最长边
+const PREVIEW_EDGE_SIZE: usize = 960;
+
+// 超过以下限制的图片不解码, 避免超大截图或多页 TIFF 占满内存
+// 原始数据的最大字节数
+pub const MAX_IMG_BYTES: usize = 64 * 1024 * 1024;
+// 单边的最大像素
+const MAX_IMG_EDGE_SIZE: u32 = 16384;
+// 总像素数的上限
+const MAX_IMG_PIXELS: u64 = 50_000_000;
+// 解码时最多分配的内存
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+// 图片压缩, 缩略图和预览图写入 blob store
+pub fn optimize_img(blobs: &BlobStore, img_bytes: &[u8]) -> Result<OptimizedImg> {
+    let img = decode_img(img_bytes)?;
+    let (width, height) = img.dimensions();
+    let img_size = format!("{}x{}", width, height);
+    let phash = dhash(&img);
+
+    let format = settings::get().thumbnail.format;
+    let thumbnail = encode_img(resize_img(&img, THUMBNAIL_EDGE_SIZE), format)?;
+    let preview = encode_img(resize_img(&img, PREVIEW_EDGE_SIZE), format)?;
+
+    Ok(OptimizedImg {
+        img_size,
+        phash,
+        thumbnail_key: blobs.put(&thumbnail, format.extension())?,
+        preview_key: blobs.put(&preview, format.extension())?,
+    })
+}
+
+// 解码图片, 并按 EXIF 中的方向旋转
+// 动图和多页 TIFF 只解码第一帧
+pub fn decode_img(img_bytes: &[u8]) -> Result<image::DynamicImage> {
+    if img_bytes.len() > MAX_IMG_BYTES {
+        return Err(Error::InvalidInput(format!(
+            "Image too large: {} bytes",
+            img_bytes.len()
+        )));
+    }
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_IMG_EDGE_SIZE);
+    limits.max_image_height = Some(MAX_IMG_EDGE_SIZE);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(img_bytes)).with_guessed_format()?;
+    reader.limits(limits);
+    let mut decoder = reader.into_decoder()?;
+
+    // 在分配像素内存之前检查尺寸
+    let (width, height) = decoder.dimensions();
+    if width as u64 * height as u64 > MAX_IMG_PIXELS {
+        return Err(Error::InvalidInput(format!(
+            "Image too large: {}x{}",
+            width, height
+        )));
+    }
+
+    let orientation = decoder.orientation()?;
+
+    let mut img = image::DynamicImage::from_decoder(decoder)?;
+    img.apply_orientation(orientation);
+    Ok(img)
+}
+
+fn encode_img(img: RgbaImage, format: ThumbnailFormat) -> Result<Vec<u8>> {
+    match format {
+        ThumbnailFormat::Png => encode_png(img),
+        ThumbnailFormat::Webp => {
+            let mut bytes = Vec::new();
+            img.write_with_encoder(WebPEncoder::new_lossless(&mut bytes))?;
+            Ok(bytes)
+        }
+        ThumbnailFormat::Avif => {
+            let mut bytes = Vec::new();
+            img.write_with_encoder(AvifEncoder::new_with_speed_quality(&mut bytes, 8, 70))?;
+            Ok(bytes)
+        }
+    }
+}
+
+// 调色板量化后的 png, 体积比直接编码小很多
+fn encode_png(img: RgbaImage) -> Result<Vec<u8>> {
+    let (width, height) = (img.width() as usize, img.height() as usize);
+    let pixels: Vec<imagequant::RGBA> = img
         .as_raw()
         .chunks(4)
         .map(|chunk| imagequant::RGBA {
@@ -25,67 +123,71 @@ pub fn optimize_img(img_bytes: &[u8]) -> Result<(String, String), String> {
     let attrs = Attributes::new();
     // 创建 imagequant 的图像实例
     let mut image = attrs
-        .new_image(pixels, new_width, new_height, 0.0)
-        .expect("Failed to create imagequant instance");
+        .new_image(pixels, width, height, 0.0)
+        .map_err(|e| Error::Other(format!("Failed to create imagequant instance: {}", e)))?;
     // 执行量化
     let mut quantized = attrs
         .quantize(&mut image)
-        .expect("Failed to quantize image");
+        .map_err(|e| Error::Other(format!("Failed to quantize image: {}", e)))?;
     // 获取量化后的像素数据
     let (palette, pixels) = quantized
         .remapped(&mut image)
-        .expect("Failed to remap image");
+        .map_err(|e| Error::Other(format!("Failed to remap image: {}", e)))?;
 
     // 将量化后的数据转换为 PngImage
     let mut encoder = lodepng::Encoder::new();
     encoder
         .set_palette(palette.as_slice())
-        .expect("encoder failed to set palette");
-    let png_vec: Vec<u8> = encoder
-        .encode(pixels.as_slice(), new_width, new_height)
-        .expect("encoder failed to encode image");
+        .map_err(|e| Error::Other(format!("Encoder failed to set palette: {}", e)))?;
+    encoder
+        .encode(pixels.as_slice(), width, height)
+        .map_err(|e| Error::Other(format!("Encoder failed to encode image: {}", e)))
+}
+
+// 缩小为 9x8 的灰度图, 每行比较相邻像素的亮度得到 64 位哈希
+fn dhash(img: &image::DynamicImage) -> u64 {
+    let gray =
+        image::imageops::resize(&img.to_luma8(), 9, 8, image::imageops::FilterType::Triangle);
 
-    Ok((STANDARD.encode(&png_vec), img_size))
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = gray.get_pixel(x, y)[0];
+            let right = gray.get_pixel(x + 1, y)[0];
+            hash <<= 1;
+            if left > right {
+                hash |= 1;
+            }
+        }
+    }
+    hash
 }
 
-const MAX_EDGE_SIZE: usize = 240;
+// 两个感知哈希之间不同的位数, 越小越相似
+pub fn hamming_distance(a: u64, b: u64) -> u32 {
+    (a ^ b).count_ones()
+}
 
-// 生成一张小尺寸的图
-fn resize_img(
-    img: image::DynamicImage,
-) -> (
-    image::ImageBuffer<image::Rgba<u8>, Vec<u8>>,
-    String,
-    (usize, usize),
-) {
+// 等比缩小到最长边不超过 max_edge_size
+fn resize_img(img: &image::DynamicImage, max_edge_size: usize) -> RgbaImage {
     let (width, height) = img.dimensions();
     let uw = width as usize;
     let uh = height as usize;
 
-    let img_size = format!("{}x{}", width, height);
-
-    let mut new_width = uw;
-    let mut new_height = uh;
-
-    if uw > MAX_EDGE_SIZE || uh > MAX_EDGE_SIZE {
-        if uw > uh {
-            new_width = MAX_EDGE_SIZE;
-            new_height = new_width * uh / uw;
+    if uw > max_edge_size || uh > max_edge_size {
+        let (new_width, new_height) = if uw > uh {
+            (max_edge_size, (max_edge_size * uh / uw).max(1))
         } else {
-            new_height = MAX_EDGE_SIZE;
-            new_width = new_height * uw / uh;
-        }
+            ((max_edge_size * uw / uh).max(1), max_edge_size)
+        };
 
-        let resized_img = image::imageops::resize(
-            &img,
+        return image::imageops::resize(
+            img,
             new_width as u32,
             new_height as u32,
             image::imageops::FilterType::Lanczos3,
         );
-
-        return (resized_img, img_size, (new_width, new_height));
     }
 
-    let rgba_img = img.to_rgba8();
-    (rgba_img, img_size, (new_width, new_height))
+    img.to_rgba8()
 }
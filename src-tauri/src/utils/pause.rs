@@ -0,0 +1,113 @@
+use lazy_static::lazy_static;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{AppHandle, Emitter};
+
+use crate::utils::error::{Error, Result};
+use crate::utils::{clipboard_read, tray};
+
+// 暂停记录剪贴板(录屏、处理密码时), 暂停期间复制的内容恢复后也不会被记录
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum PauseState {
+    Running,
+    // 自动恢复的时间, None 表示一直暂停到手动恢复
+    Paused(Option<SystemTime>),
+}
+
+lazy_static! {
+    static ref STATE: Mutex<PauseState> = Mutex::new(PauseState::Running);
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CaptureStatus {
+    pub paused: bool,
+    // 自动恢复的时间(unix 毫秒), 一直暂停时为空
+    pub resume_at: Option<u64>,
+}
+
+pub fn is_paused() -> bool {
+    *STATE.lock().unwrap() != PauseState::Running
+}
+
+pub fn status() -> CaptureStatus {
+    match *STATE.lock().unwrap() {
+        PauseState::Running => CaptureStatus {
+            paused: false,
+            resume_at: None,
+        },
+        PauseState::Paused(until) => CaptureStatus {
+            paused: true,
+            resume_at: until
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64),
+        },
+    }
+}
+
+// 暂停 minutes 分钟, None 表示一直暂停
+pub fn pause(app: &AppHandle, minutes: Option<u64>) -> Result<CaptureStatus> {
+    if minutes == Some(0) {
+        return Err(Error::InvalidInput("Minutes must be positive".to_string()));
+    }
+    let until = minutes.map(|minutes| SystemTime::now() + Duration::from_secs(minutes * 60));
+    *STATE.lock().unwrap() = PauseState::Paused(until);
+    tracing::info!(?minutes, "clipboard capture paused");
+
+    if let Some(until) = until {
+        let app = app.clone();
+        std::thread::spawn(move || {
+            if let Ok(wait) = until.duration_since(SystemTime::now()) {
+                std::thread::sleep(wait);
+            }
+            // 期间可能已经手动恢复或重新暂停, 只结束这一次暂停
+            if *STATE.lock().unwrap() == PauseState::Paused(Some(until)) {
+                resume(&app);
+            }
+        });
+    }
+    Ok(notify(app))
+}
+
+pub fn resume(app: &AppHandle) -> CaptureStatus {
+    // 先跳过当前内容再恢复, 避免轮询线程抢先记录暂停期间复制的内容
+    clipboard_read::skip_current();
+    *STATE.lock().unwrap() = PauseState::Running;
+    tracing::info!("clipboard capture resumed");
+    notify(app)
+}
+
+// 快捷键在暂停和恢复之间切换, 暂停时不限时间
+pub fn toggle(app: &AppHandle) -> Result<CaptureStatus> {
+    if is_paused() {
+        Ok(resume(app))
+    } else {
+        pause(app, None)
+    }
+}
+
+// 更新托盘并通知渲染进程
+fn notify(app: &AppHandle) -> CaptureStatus {
+    let status = status();
+    if let Err(e) = tray::refresh(app, &status) {
+        tracing::error!(error = %e, "failed to refresh tray");
+    }
+    let _ = app.emit("capture-status", status.clone());
+    status
+}
+
+#[tauri::command]
+pub async fn get_capture_status() -> Result<CaptureStatus> {
+    Ok(status())
+}
+
+#[tauri::command]
+pub async fn pause_capture(app: AppHandle, minutes: Option<u64>) -> Result<CaptureStatus> {
+    pause(&app, minutes)
+}
+
+#[tauri::command]
+pub async fn resume_capture(app: AppHandle) -> Result<CaptureStatus> {
+    Ok(resume(&app))
+}
@@ -0,0 +1,444 @@
+use lazy_static::lazy_static;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use tauri::{Manager, State};
+
+use crate::utils::error::Result;
+use crate::utils::logger;
+
+// 当前配置, 剪贴板轮询、同步等后台线程随时读取, 所以保留为全局变量
+lazy_static! {
+    static ref SETTINGS: Mutex<Settings> = Mutex::new(Settings::default());
+}
+
+// 配置文件的路径, 作为 tauri 的 managed state, 保存配置时使用
+pub struct SettingsFile(PathBuf);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SyncSettings {
+    pub enabled: bool,
+    // 本地/挂载目录的路径, 或者 http(s):// 开头的 WebDAV 地址
+    pub target: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // 用于加密变更日志, 所有设备需一致
+    pub passphrase: String,
+    // 同步间隔, 单位秒
+    pub interval_secs: u64,
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        SyncSettings {
+            enabled: false,
+            target: String::new(),
+            username: None,
+            password: None,
+            passphrase: String::new(),
+            interval_secs: 60,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ApiSettings {
+    // 是否开启本地 API, 修改后重启生效
+    pub enabled: bool,
+    // 请求时需携带的 token, 首次启动时生成
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OcrSettings {
+    // 是否识别图片中的文字, 开启后重启时会补齐历史图片
+    pub enabled: bool,
+    // 识别的语言, 按优先级排列
+    pub languages: Vec<String>,
+}
+
+impl Default for OcrSettings {
+    fn default() -> Self {
+        OcrSettings {
+            enabled: false,
+            languages: vec!["zh-Hans".to_string(), "en-US".to_string()],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageDedupSettings {
+    // 是否合并看起来相同的图片
+    pub enabled: bool,
+    // 感知哈希最多相差的位数(0-64), 0 表示哈希完全一致才合并
+    // 截图的局部改动(光标、文字)也只差几位, 默认只合并哈希一致的图片
+    pub max_distance: u32,
+}
+
+impl Default for ImageDedupSettings {
+    fn default() -> Self {
+        ImageDedupSettings {
+            enabled: true,
+            max_distance: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ThumbnailFormat {
+    #[default]
+    Png,
+    Webp,
+    Avif,
+}
+
+impl ThumbnailFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ThumbnailFormat::Png => "png",
+            ThumbnailFormat::Webp => "webp",
+            ThumbnailFormat::Avif => "avif",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ThumbnailSettings {
+    // 缩略图和预览图的格式, 只影响之后生成的图片
+    pub format: ThumbnailFormat,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PasteSettings {
+    // 粘贴图片时是否额外提供 jpeg 格式(默认提供 tiff 和 png)
+    pub jpeg: bool,
+    // jpeg 质量(1-100)
+    pub jpeg_quality: u8,
+}
+
+impl Default for PasteSettings {
+    fn default() -> Self {
+        PasteSettings {
+            jpeg: false,
+            jpeg_quality: 85,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LinkPreviewSettings {
+    // 是否抓取链接的标题和图标, 会访问复制的链接, 默认关闭
+    pub enabled: bool,
+    // 不抓取的域名, 同时匹配子域名
+    pub blocked_domains: Vec<String>,
+    // 两次抓取之间的最小间隔, 单位毫秒
+    pub min_interval_ms: u64,
+}
+
+impl Default for LinkPreviewSettings {
+    fn default() -> Self {
+        LinkPreviewSettings {
+            enabled: false,
+            blocked_domains: Vec::new(),
+            min_interval_ms: 2000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpirySettings {
+    // 记录过期时, 如果剪贴板中还是这条记录, 同时清空剪贴板
+    pub clear_clipboard: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+    Trace,
+}
+
+impl LogLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogLevel::Error => "error",
+            LogLevel::Warn => "warn",
+            LogLevel::Info => "info",
+            LogLevel::Debug => "debug",
+            LogLevel::Trace => "trace",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    // 当前设备的唯一标识, 首次启动时生成
+    pub device_id: String,
+    pub sync: SyncSettings,
+    pub api: ApiSettings,
+    pub ocr: OcrSettings,
+    pub image_dedup: ImageDedupSettings,
+    pub thumbnail: ThumbnailSettings,
+    pub paste: PasteSettings,
+    pub link_preview: LinkPreviewSettings,
+    pub expiry: ExpirySettings,
+    // 日志级别, 修改后立即生效
+    pub log_level: LogLevel,
+}
+
+pub fn init(app: &tauri::App) -> Result<()> {
+    let app_dir = app.app_handle().path().app_data_dir()?;
+    app.manage(init_at(&app_dir)?);
+    Ok(())
+}
+
+pub fn init_at(app_dir: &Path) -> Result<SettingsFile> {
+    std::fs::create_dir_all(app_dir)?;
+    let path = app_dir.join("settings.json");
+    *current() = load(&path);
+    Ok(SettingsFile(path))
+}
+
+// 持有锁的线程 panic 时锁可能处于中毒状态, 配置本身仍然可用
+fn current() -> MutexGuard<'static, Settings> {
+    SETTINGS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// 读取配置并补齐首次启动时生成的字段
+// 文件无法读取或解析时使用默认配置, 但不写回, 避免覆盖用户的口令和 token
+fn load(path: &Path) -> Settings {
+    let mut settings = match std::fs::read_to_string(path) {
+        Ok(content) => match serde_json::from_str::<Settings>(&content) {
+            Ok(settings) => settings,
+            Err(e) => {
+                tracing::error!(path = %path.display(), error = %e, "failed to parse settings, using defaults");
+                // 之后在设置页保存会覆盖原文件, 先留一份副本便于手动修复
+                let _ = std::fs::copy(path, path.with_extension("json.invalid"));
+                return generate_ids(Settings::default());
+            }
+        },
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Settings::default(),
+        Err(e) => {
+            tracing::error!(path = %path.display(), error = %e, "failed to read settings, using defaults");
+            return generate_ids(Settings::default());
+        }
+    };
+
+    if settings.device_id.is_empty() || settings.api.token.is_empty() {
+        settings = generate_ids(settings);
+        if let Err(e) = write(path, &settings) {
+            tracing::error!(error = %e, "failed to save settings");
+        }
+    }
+    settings
+}
+
+fn generate_ids(mut settings: Settings) -> Settings {
+    if settings.device_id.is_empty() {
+        settings.device_id = uuid::Uuid::new_v4().simple().to_string();
+    }
+    if settings.api.token.is_empty() {
+        settings.api.token = uuid::Uuid::new_v4().simple().to_string();
+    }
+    settings
+}
+
+pub fn get() -> Settings {
+    current().clone()
+}
+
+// 配置中有 API token 和同步口令, 只允许当前用户读写
+fn write(path: &Path, settings: &Settings) -> Result<()> {
+    let content = serde_json::to_string_pretty(settings)?;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    // 文件已经存在时 mode 不生效
+    file.set_permissions(std::fs::Permissions::from_mode(0o600))?;
+    file.write_all(content.as_bytes())?;
+    Ok(())
+}
+
+// 返回给前端的配置去掉 API token、同步口令和 WebDAV 密码, 保存时由 apply_update 补回
+fn redact(mut settings: Settings) -> Settings {
+    settings.api.token = String::new();
+    settings.sync.passphrase = String::new();
+    settings.sync.password = None;
+    settings
+}
+
+#[tauri::command]
+pub async fn get_settings() -> Result<Settings> {
+    Ok(redact(get()))
+}
+
+// device_id 不允许被前端修改, 没有传 api token、同步口令和 WebDAV 密码时保留原来的
+fn apply_update(current: &Settings, mut settings: Settings) -> Settings {
+    settings.device_id = current.device_id.clone();
+    if settings.api.token.is_empty() {
+        settings.api.token = current.api.token.clone();
+    }
+    if settings.sync.passphrase.is_empty() {
+        settings.sync.passphrase = current.sync.passphrase.clone();
+    }
+    if settings.sync.password.is_none() {
+        settings.sync.password = current.sync.password.clone();
+    }
+    generate_ids(settings)
+}
+
+#[tauri::command]
+pub async fn update_settings(
+    file: State<'_, SettingsFile>,
+    settings: Settings,
+) -> Result<Settings> {
+    let settings = apply_update(&current(), settings);
+    write(&file.0, &settings)?;
+    *current() = settings.clone();
+    logger::set_level(settings.log_level);
+    Ok(redact(settings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_file_is_created() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let settings = load(&path);
+        assert!(!settings.device_id.is_empty());
+        assert!(!settings.api.token.is_empty());
+
+        let saved: Settings =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.device_id, settings.device_id);
+        assert_eq!(load(&path).api.token, settings.api.token);
+    }
+
+    #[test]
+    fn file_is_private() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let mode = |path: &Path| std::fs::metadata(path).unwrap().permissions().mode() & 0o777;
+
+        std::fs::write(&path, "{}").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        write(&path, &Settings::default()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+
+        std::fs::remove_file(&path).unwrap();
+        write(&path, &Settings::default()).unwrap();
+        assert_eq!(mode(&path), 0o600);
+    }
+
+    #[test]
+    fn invalid_file_is_kept() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        let content = r#"{"device_id": "abc", "sync": {"passphrase": "secret",}}"#;
+        std::fs::write(&path, content).unwrap();
+
+        let settings = load(&path);
+        assert!(!settings.device_id.is_empty());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), content);
+        let copy = std::fs::read_to_string(path.with_extension("json.invalid")).unwrap();
+        assert_eq!(copy, content);
+    }
+
+    #[test]
+    fn missing_fields_are_filled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("settings.json");
+        std::fs::write(
+            &path,
+            r#"{"device_id": "abc", "sync": {"passphrase": "secret"}}"#,
+        )
+        .unwrap();
+
+        let settings = load(&path);
+        assert_eq!(settings.device_id, "abc");
+        assert_eq!(settings.sync.passphrase, "secret");
+        assert!(!settings.api.token.is_empty());
+
+        let saved: Settings =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(saved.sync.passphrase, "secret");
+        assert_eq!(saved.api.token, settings.api.token);
+    }
+
+    #[test]
+    fn update_keeps_device_id_and_token() {
+        let current = generate_ids(Settings::default());
+
+        let incoming = Settings {
+            device_id: "changed".to_string(),
+            api: ApiSettings {
+                enabled: true,
+                token: String::new(),
+            },
+            ..Settings::default()
+        };
+        let updated = apply_update(&current, incoming);
+        assert_eq!(updated.device_id, current.device_id);
+        assert_eq!(updated.api.token, current.api.token);
+        assert!(updated.api.enabled);
+
+        // 可以修改 token
+        let mut incoming = current.clone();
+        incoming.api.token = "new token".to_string();
+        assert_eq!(apply_update(&current, incoming).api.token, "new token");
+
+        // 原来也没有 token 时重新生成
+        let updated = apply_update(&Settings::default(), Settings::default());
+        assert!(!updated.api.token.is_empty());
+    }
+
+    #[test]
+    fn secrets_are_redacted_and_kept() {
+        let mut current = generate_ids(Settings::default());
+        current.sync.passphrase = "passphrase".to_string();
+        current.sync.username = Some("user".to_string());
+        current.sync.password = Some("password".to_string());
+
+        let redacted = redact(current.clone());
+        assert!(redacted.api.token.is_empty());
+        assert!(redacted.sync.passphrase.is_empty());
+        assert_eq!(redacted.sync.password, None);
+        assert_eq!(redacted.sync.username.as_deref(), Some("user"));
+        assert_eq!(redacted.device_id, current.device_id);
+
+        // 前端把读到的配置原样保存时密钥不变
+        let updated = apply_update(&current, redacted);
+        assert_eq!(updated.api.token, current.api.token);
+        assert_eq!(updated.sync.passphrase, "passphrase");
+        assert_eq!(updated.sync.password.as_deref(), Some("password"));
+
+        let mut incoming = redact(current.clone());
+        incoming.sync.passphrase = "changed".to_string();
+        incoming.sync.password = Some("changed".to_string());
+        let updated = apply_update(&current, incoming);
+        assert_eq!(updated.sync.passphrase, "changed");
+        assert_eq!(updated.sync.password.as_deref(), Some("changed"));
+    }
+}
@@ -0,0 +1,443 @@
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use tauri::State;
+
+use crate::utils::db::{Database, SIZE_EXPR};
+use crate::utils::error::Result;
+
+// 统计只包含未删除的记录, 时间段按本地时间划分
+// 新增记录数和来源应用只统计最近 days 天内新增的记录, 其余统计覆盖全部历史
+
+// 时间段的粒度
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    #[default]
+    Day,
+    // ISO 8601 周, 周一开始, 格式为 2026-W07
+    // 跨年的周归到周四所在的年份, 如 2027-01-01 属于 2026-W53
+    Week,
+    Month,
+}
+
+impl Bucket {
+    // 时间段的 SQL 表达式, 系统自带的 SQLite 不一定支持 %G 和 %V, 按周四所在的日期计算 ISO 周
+    fn period_expr(&self) -> &'static str {
+        match self {
+            Bucket::Day => "strftime('%Y-%m-%d', created_at, 'localtime')",
+            Bucket::Week => {
+                "printf('%s-W%02d',
+                     strftime('%Y', created_at, 'localtime', '-3 days', 'weekday 4'),
+                     (strftime('%j', created_at, 'localtime', '-3 days', 'weekday 4') - 1) / 7 + 1)"
+            }
+            Bucket::Month => "strftime('%Y-%m', created_at, 'localtime')",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StatisticsParams {
+    // 最近多少天, 0 表示全部
+    pub days: u32,
+    pub bucket: Bucket,
+    // 来源应用和常用记录的数量上限
+    pub limit: u32,
+}
+
+impl Default for StatisticsParams {
+    fn default() -> Self {
+        Self {
+            days: 30,
+            bucket: Bucket::Day,
+            limit: 10,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeriodCount {
+    pub period: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SourceAppCount {
+    pub source_app: String,
+    pub count: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TypeCount {
+    pub record_type: String,
+    pub count: i64,
+    // 内容大小, 单位 bytes, 图片按解码后的原图估算
+    pub size_bytes: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImageStorage {
+    pub count: i64,
+    // 原图的大小和缩略图、预览图占用的空间, 单位 bytes
+    // 只保存了大小的图片按原图的大小计算
+    pub original_bytes: i64,
+    pub thumbnail_bytes: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReusedRecord {
+    pub id: i64,
+    pub record_type: String,
+    // 文本和文件路径的前 100 个字符, 图片为空字符串
+    pub preview: String,
+    pub thumbnail_key: Option<String>,
+    pub copy_count: i64,
+    pub paste_count: i64,
+    pub last_used_at: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Statistics {
+    pub record_count: i64,
+    // 累计的复制和从面板粘贴次数
+    pub copy_count: i64,
+    pub paste_count: i64,
+    // 每个时间段新增的记录数, 按时间排列, 没有记录的时间段不返回
+    pub items_per_period: Vec<PeriodCount>,
+    pub top_source_apps: Vec<SourceAppCount>,
+    pub record_types: Vec<TypeCount>,
+    pub image_storage: ImageStorage,
+    // 复制和粘贴次数最多的记录, 只使用过一次的不算
+    pub most_reused: Vec<ReusedRecord>,
+}
+
+#[tauri::command]
+pub async fn get_statistics(
+    db: State<'_, Database>,
+    params: StatisticsParams,
+) -> Result<Statistics> {
+    db.run(move |db| statistics(db, &params)).await
+}
+
+pub fn statistics(db: &Database, params: &StatisticsParams) -> Result<Statistics> {
+    // 相同的缩略图只保存一份, 按 key 去重后统计文件大小
+    let thumbnail_bytes = db
+        .read(image_blob_keys)?
+        .iter()
+        .filter_map(|key| db.blobs().size(key))
+        .sum();
+
+    let since = (params.days > 0).then(|| format!("-{} days", params.days));
+    db.read(|conn| {
+        let (record_count, copy_count, paste_count) = conn.query_row(
+            "SELECT COUNT(*), COALESCE(SUM(copy_count), 0), COALESCE(SUM(paste_count), 0)
+             FROM record WHERE deleted_at IS NULL",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        let image_storage = conn.query_row(
+            &format!(
+                "SELECT COUNT(*), COALESCE(SUM({}), 0)
+                 FROM record WHERE deleted_at IS NULL AND record_type = 'image'",
+                SIZE_EXPR
+            ),
+            [],
+            |row| {
+                Ok(ImageStorage {
+                    count: row.get(0)?,
+                    original_bytes: row.get(1)?,
+                    thumbnail_bytes,
+                })
+            },
+        )?;
+
+        Ok(Statistics {
+            record_count,
+            copy_count,
+            paste_count,
+            items_per_period: items_per_period(conn, params.bucket, &since)?,
+            top_source_apps: top_source_apps(conn, &since, params.limit)?,
+            record_types: record_types(conn)?,
+            image_storage,
+            most_reused: most_reused(conn, params.limit)?,
+        })
+    })
+}
+
+fn image_blob_keys(conn: &Connection) -> rusqlite::Result<HashSet<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT thumbnail_key FROM record
+         WHERE deleted_at IS NULL AND record_type = 'image' AND thumbnail_key IS NOT NULL
+         UNION
+         SELECT preview_key FROM record
+         WHERE deleted_at IS NULL AND record_type = 'image' AND preview_key IS NOT NULL",
+    )?;
+    let keys = stmt.query_map([], |row| row.get(0))?;
+    keys.collect()
+}
+
+// since 为 SQLite 的时间修饰符, 如 -30 days, None 表示全部
+fn items_per_period(
+    conn: &Connection,
+    bucket: Bucket,
+    since: &Option<String>,
+) -> rusqlite::Result<Vec<PeriodCount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} AS period, COUNT(*) FROM record
+         WHERE deleted_at IS NULL
+           AND (?1 IS NULL OR created_at >= strftime('%Y-%m-%d %H:%M:%f', 'now', ?1))
+         GROUP BY period ORDER BY period",
+        bucket.period_expr()
+    ))?;
+    let rows = stmt.query_map([since], |row| {
+        Ok(PeriodCount {
+            period: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn top_source_apps(
+    conn: &Connection,
+    since: &Option<String>,
+    limit: u32,
+) -> rusqlite::Result<Vec<SourceAppCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT source_app, COUNT(*) AS count FROM record
+         WHERE deleted_at IS NULL AND source_app IS NOT NULL
+           AND (?1 IS NULL OR created_at >= strftime('%Y-%m-%d %H:%M:%f', 'now', ?1))
+         GROUP BY source_app ORDER BY count DESC, source_app LIMIT ?2",
+    )?;
+    let rows = stmt.query_map((since, limit), |row| {
+        Ok(SourceAppCount {
+            source_app: row.get(0)?,
+            count: row.get(1)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn record_types(conn: &Connection) -> rusqlite::Result<Vec<TypeCount>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT record_type, COUNT(*) AS count, COALESCE(SUM({}), 0) FROM record
+         WHERE deleted_at IS NULL
+         GROUP BY record_type ORDER BY count DESC, record_type",
+        SIZE_EXPR
+    ))?;
+    let rows = stmt.query_map([], |row| {
+        Ok(TypeCount {
+            record_type: row.get(0)?,
+            count: row.get(1)?,
+            size_bytes: row.get(2)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn most_reused(conn: &Connection, limit: u32) -> rusqlite::Result<Vec<ReusedRecord>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, record_type,
+             CASE WHEN record_type = 'image' THEN '' ELSE substr(value, 1, 100) END,
+             thumbnail_key, copy_count, paste_count, last_used_at
+         FROM record
+         WHERE deleted_at IS NULL AND copy_count + paste_count > 1
+         ORDER BY copy_count + paste_count DESC, last_used_at DESC, id DESC LIMIT ?1",
+    )?;
+    let rows = stmt.query_map([limit], |row| {
+        Ok(ReusedRecord {
+            id: row.get(0)?,
+            record_type: row.get(1)?,
+            preview: row.get(2)?,
+            thumbnail_key: row.get(3)?,
+            copy_count: row.get(4)?,
+            paste_count: row.get(5)?,
+            last_used_at: row.get(6)?,
+        })
+    })?;
+    rows.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::{self, RecordInput};
+    use crate::utils::img_worker::placeholder_record;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    fn open() -> (tempfile::TempDir, Database) {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        (dir, db)
+    }
+
+    fn from_app(value: &str, source_app: &str) -> RecordInput {
+        RecordInput {
+            source_app: Some(source_app.to_string()),
+            ..RecordInput::text(value)
+        }
+    }
+
+    fn set_created_at(db: &Database, id: i64, created_at: &str) {
+        db.write(|conn| {
+            conn.execute(
+                "UPDATE record SET created_at = ?1 WHERE id = ?2",
+                (created_at, id),
+            )
+        })
+        .unwrap();
+    }
+
+    fn params(days: u32, bucket: Bucket) -> StatisticsParams {
+        StatisticsParams {
+            days,
+            bucket,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn aggregates_cover_every_field() {
+        let (_dir, db) = open();
+        let thumbnail = db.blobs().put(&[1; 40], "png").unwrap();
+
+        let hello = db
+            .add_record(from_app("hello", "com.apple.Safari"))
+            .unwrap();
+        db.add_record(RecordInput::text("hello")).unwrap();
+        db.record_paste(hello).unwrap();
+        db.add_record(from_app("world", "com.apple.Safari"))
+            .unwrap();
+        let old = db
+            .add_record(from_app("old", "com.apple.Terminal"))
+            .unwrap();
+        set_created_at(&db, old, "2020-03-04 12:00:00.000");
+        // 10 字节的图片, base64 带两个填充字符; 两张图片共用同一个缩略图
+        let image = |value: String| RecordInput {
+            thumbnail_key: Some(thumbnail.clone()),
+            ..RecordInput::text(&value).with_type("image")
+        };
+        db.add_record(image(STANDARD.encode([1; 10]))).unwrap();
+        db.add_record(image(STANDARD.encode([2; 10]))).unwrap();
+        // 只保存了大小的图片按原图大小计算
+        db.add_record(placeholder_record(5000)).unwrap();
+        db.add_record(RecordInput {
+            size: Some(7),
+            ..RecordInput::text("/tmp/a.txt").with_type("file")
+        })
+        .unwrap();
+        let deleted = db.add_record(RecordInput::text("deleted")).unwrap();
+        db.soft_delete(&[deleted], false).unwrap();
+
+        let s = statistics(&db, &StatisticsParams::default()).unwrap();
+        assert_eq!((s.record_count, s.copy_count, s.paste_count), (7, 8, 1));
+
+        let today: String = db
+            .read(|conn| {
+                conn.query_row(
+                    "SELECT strftime('%Y-%m-%d', 'now', 'localtime')",
+                    [],
+                    |row| row.get(0),
+                )
+            })
+            .unwrap();
+        assert_eq!(s.items_per_period.len(), 1);
+        assert_eq!(s.items_per_period[0].period, today);
+        assert_eq!(s.items_per_period[0].count, 6);
+
+        let apps: Vec<_> = s
+            .top_source_apps
+            .iter()
+            .map(|a| (a.source_app.as_str(), a.count))
+            .collect();
+        assert_eq!(apps, vec![("com.apple.Safari", 2)]);
+
+        let types: Vec<_> = s
+            .record_types
+            .iter()
+            .map(|t| (t.record_type.as_str(), t.count, t.size_bytes))
+            .collect();
+        assert_eq!(
+            types,
+            vec![("image", 3, 5020), ("text", 3, 13), ("file", 1, 7)]
+        );
+
+        assert_eq!(s.image_storage.count, 3);
+        assert_eq!(s.image_storage.original_bytes, 5020);
+        assert_eq!(s.image_storage.thumbnail_bytes, 40);
+
+        assert_eq!(s.most_reused.len(), 1);
+        let reused = &s.most_reused[0];
+        assert_eq!((reused.id, reused.preview.as_str()), (hello, "hello"));
+        assert_eq!((reused.copy_count, reused.paste_count), (2, 1));
+        assert!(reused.last_used_at.is_some());
+    }
+
+    #[test]
+    fn days_filter_only_limits_new_records_and_apps() {
+        let (_dir, db) = open();
+        db.add_record(from_app("new", "com.apple.Safari")).unwrap();
+        let old = db
+            .add_record(from_app("old", "com.apple.Terminal"))
+            .unwrap();
+        set_created_at(&db, old, "2020-03-04 12:00:00.000");
+
+        let recent = statistics(&db, &params(30, Bucket::Month)).unwrap();
+        assert_eq!(recent.record_count, 2);
+        assert_eq!(recent.items_per_period.len(), 1);
+        assert_eq!(recent.top_source_apps.len(), 1);
+        assert_eq!(recent.top_source_apps[0].source_app, "com.apple.Safari");
+
+        let all = statistics(&db, &params(0, Bucket::Month)).unwrap();
+        assert_eq!(all.record_count, 2);
+        assert_eq!(all.items_per_period.len(), 2);
+        assert_eq!(all.items_per_period[0].period, "2020-03");
+        assert_eq!(all.top_source_apps.len(), 2);
+    }
+
+    // 时间按本地时间划分, 都用中午的时间, 时区偏移不会改变日期所在的周和月
+    #[test]
+    fn periods_use_iso_weeks() {
+        let (_dir, db) = open();
+        for (value, created_at) in [
+            // 周四, 2026 年的第一周
+            ("a", "2026-01-01 12:00:00.000"),
+            // 周三, 同属 2026 年的第一周
+            ("b", "2025-12-31 12:00:00.000"),
+            // 周五, 属于 2026 年的最后一周
+            ("c", "2027-01-01 12:00:00.000"),
+            ("d", "2026-03-18 12:00:00.000"),
+        ] {
+            let id = db.add_record(RecordInput::text(value)).unwrap();
+            set_created_at(&db, id, created_at);
+        }
+
+        let periods = |bucket| -> Vec<(String, i64)> {
+            statistics(&db, &params(0, bucket))
+                .unwrap()
+                .items_per_period
+                .into_iter()
+                .map(|p| (p.period, p.count))
+                .collect()
+        };
+        let expected = |items: &[(&str, i64)]| -> Vec<(String, i64)> {
+            items.iter().map(|(p, c)| (p.to_string(), *c)).collect()
+        };
+        assert_eq!(
+            periods(Bucket::Week),
+            expected(&[("2026-W01", 2), ("2026-W12", 1), ("2026-W53", 1)])
+        );
+        assert_eq!(
+            periods(Bucket::Month),
+            expected(&[
+                ("2025-12", 1),
+                ("2026-01", 1),
+                ("2026-03", 1),
+                ("2027-01", 1)
+            ])
+        );
+        assert_eq!(periods(Bucket::Day)[0].0, "2025-12-31");
+    }
+}
@@ -0,0 +1,1352 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use lazy_static::lazy_static;
+use rusqlite::OptionalExtension;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+
+use crate::utils::classify::classify;
+use crate::utils::db::{self, Database};
+use crate::utils::error::{Error, Result};
+use crate::utils::optimize_img::{optimize_img, OptimizedImg};
+use crate::utils::settings::{self, SyncSettings};
+
+lazy_static! {
+    // 保证同一时间只有一个同步在进行(定时同步和手动同步)
+    static ref SYNC_LOCK: Mutex<()> = Mutex::new(());
+    // 派生密钥比较慢, 口令和盐不变时复用上次的结果
+    static ref KEY_CACHE: Mutex<Option<(String, Vec<u8>, Aes256Gcm)>> = Mutex::new(None);
+}
+
+const LOG_EXT: &str = ".log";
+// 同步目录中保存密钥派生用的盐, 由第一个同步的设备生成
+const SALT_FILE: &str = "salt";
+// 日志行的格式版本
+const LINE_PREFIX: &str = "2:";
+
+#[cfg(not(test))]
+const KDF_ROUNDS: u32 = 600_000;
+#[cfg(test)]
+const KDF_ROUNDS: u32 = 1_000;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum ChangeOp {
+    #[default]
+    Upsert,
+    Delete,
+}
+
+// 写入变更日志的一条记录, 通过 record_type + 内容哈希在各设备间识别同一条记录
+#[derive(Debug, Serialize, Deserialize)]
+struct ChangeEntry {
+    op: ChangeOp,
+    record_type: String,
+    value: String,
+    // 删除时为被删除内容的哈希, 修改内容时为修改前的哈希
+    hash: Option<String>,
+    size: Option<i64>,
+    img_size: Option<String>,
+    favorite: bool,
+    favorite_at: Option<String>,
+    created_at: String,
+    updated_at: String,
+    // 变更发生的时间, 删除和恢复按它判断先后
+    changed_at: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncStatus {
+    // syncing | idle | error
+    state: String,
+    pushed: usize,
+    merged: usize,
+    // 无法解密而跳过的日志行数
+    skipped: usize,
+    message: Option<String>,
+}
+
+// 拉取其他设备日志的结果, 单个设备失败不影响其他设备
+#[derive(Debug, Default)]
+struct PullReport {
+    merged: usize,
+    skipped: usize,
+    warnings: Vec<String>,
+}
+
+// 同步目录的抽象, 每个设备只追加写自己的日志文件 `<device_id>.log`
+trait Backend {
+    fn list_devices(&self) -> Result<Vec<String>>;
+    fn read_log(&self, device_id: &str) -> Result<String>;
+    fn append_log(&self, device_id: &str, lines: &str) -> Result<()>;
+    // 盐不存在时返回 None
+    fn read_salt(&self) -> Result<Option<String>>;
+    // 只在盐不存在时写入, 已存在时保留原来的
+    fn create_salt(&self, salt: &str) -> Result<()>;
+}
+
+struct FolderBackend {
+    dir: PathBuf,
+}
+
+impl Backend for FolderBackend {
+    fn list_devices(&self) -> Result<Vec<String>> {
+        let entries = std::fs::read_dir(&self.dir)?;
+        let devices = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().to_string_lossy().into_owned();
+                name.strip_suffix(LOG_EXT).map(|s| s.to_string())
+            })
+            .collect();
+        Ok(devices)
+    }
+
+    fn read_log(&self, device_id: &str) -> Result<String> {
+        let path = self.dir.join(format!("{}{}", device_id, LOG_EXT));
+        match std::fs::read_to_string(path) {
+            Ok(content) => Ok(content),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn append_log(&self, device_id: &str, lines: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(format!("{}{}", device_id, LOG_EXT));
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(file.write_all(lines.as_bytes())?)
+    }
+
+    fn read_salt(&self) -> Result<Option<String>> {
+        match std::fs::read_to_string(self.dir.join(SALT_FILE)) {
+            Ok(salt) => Ok(Some(salt)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn create_salt(&self, salt: &str) -> Result<()> {
+        std::fs::create_dir_all(&self.dir)?;
+        let path = self.dir.join(SALT_FILE);
+        match OpenOptions::new().write(true).create_new(true).open(path) {
+            Ok(mut file) => Ok(file.write_all(salt.as_bytes())?),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+struct WebDavBackend {
+    // 以 / 结尾的目录地址
+    base_url: String,
+    username: Option<String>,
+    password: Option<String>,
+    client: reqwest::blocking::Client,
+}
+
+impl WebDavBackend {
+    fn new(settings: &SyncSettings) -> Result<Self> {
+        let mut base_url = settings.target.clone();
+        if !base_url.ends_with('/') {
+            base_url.push('/');
+        }
+        let client = reqwest::blocking::Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()?;
+        Ok(WebDavBackend {
+            base_url,
+            username: settings.username.clone(),
+            password: settings.password.clone(),
+            client,
+        })
+    }
+
+    fn request(&self, method: &str, url: &str) -> Result<reqwest::blocking::RequestBuilder> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())
+            .map_err(|e| Error::Sync(e.to_string()))?;
+        let mut builder = self.client.request(method, url);
+        if let Some(username) = &self.username {
+            builder = builder.basic_auth(username, self.password.as_ref());
+        }
+        Ok(builder)
+    }
+
+    fn log_url(&self, device_id: &str) -> String {
+        format!("{}{}{}", self.base_url, device_id, LOG_EXT)
+    }
+
+    fn salt_url(&self) -> String {
+        format!("{}{}", self.base_url, SALT_FILE)
+    }
+}
+
+impl Backend for WebDavBackend {
+    fn list_devices(&self) -> Result<Vec<String>> {
+        let resp = self
+            .request("PROPFIND", &self.base_url)?
+            .header("Depth", "1")
+            .send()?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !resp.status().is_success() {
+            return Err(Error::Sync(format!(
+                "WebDAV PROPFIND failed: {}",
+                resp.status()
+            )));
+        }
+        let body = resp.text()?;
+        Ok(parse_propfind_devices(&body))
+    }
+
+    fn read_log(&self, device_id: &str) -> Result<String> {
+        let resp = self.request("GET", &self.log_url(device_id))?.send()?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(String::new());
+        }
+        if !resp.status().is_success() {
+            return Err(Error::Sync(format!("WebDAV GET failed: {}", resp.status())));
+        }
+        Ok(resp.text()?)
+    }
+
+    fn append_log(&self, device_id: &str, lines: &str) -> Result<()> {
+        // 目录已存在时返回 405, 忽略即可
+        let _ = self.request("MKCOL", &self.base_url)?.send();
+
+        // WebDAV 不支持追加写, 读出自己的日志后整体覆盖
+        let mut content = self.read_log(device_id)?;
+        content.push_str(lines);
+        let resp = self
+            .request("PUT", &self.log_url(device_id))?
+            .body(content)
+            .send()?;
+        if !resp.status().is_success() {
+            return Err(Error::Sync(format!("WebDAV PUT failed: {}", resp.status())));
+        }
+        Ok(())
+    }
+
+    fn read_salt(&self) -> Result<Option<String>> {
+        let resp = self.request("GET", &self.salt_url())?.send()?;
+        if resp.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !resp.status().is_success() {
+            return Err(Error::Sync(format!("WebDAV GET failed: {}", resp.status())));
+        }
+        Ok(Some(resp.text()?))
+    }
+
+    fn create_salt(&self, salt: &str) -> Result<()> {
+        let _ = self.request("MKCOL", &self.base_url)?.send();
+
+        // If-None-Match: * 保证不会覆盖其他设备已经写入的盐
+        let resp = self
+            .request("PUT", &self.salt_url())?
+            .header("If-None-Match", "*")
+            .body(salt.to_string())
+            .send()?;
+        if resp.status() == reqwest::StatusCode::PRECONDITION_FAILED {
+            return Ok(());
+        }
+        if !resp.status().is_success() {
+            return Err(Error::Sync(format!("WebDAV PUT failed: {}", resp.status())));
+        }
+        Ok(())
+    }
+}
+
+// 从 PROPFIND 的响应中取出所有 `<href>` 里的日志文件名
+fn parse_propfind_devices(body: &str) -> Vec<String> {
+    let mut devices = Vec::new();
+    let mut rest = body;
+    while let Some(start) = rest.find("href>") {
+        rest = &rest[start + "href>".len()..];
+        let Some(end) = rest.find('<') else {
+            break;
+        };
+        let href = rest[..end].trim();
+        let name = href.rsplit('/').next().unwrap_or_default();
+        if let Some(device_id) = name.strip_suffix(LOG_EXT) {
+            if !device_id.is_empty() && !devices.iter().any(|d| d == device_id) {
+                devices.push(device_id.to_string());
+            }
+        }
+        rest = &rest[end..];
+    }
+    devices
+}
+
+fn open_backend(settings: &SyncSettings) -> Result<Box<dyn Backend>> {
+    let target = settings.target.trim();
+    if target.is_empty() {
+        return Err(Error::Sync("Sync target not configured".to_string()));
+    }
+    if target.starts_with("http://") || target.starts_with("https://") {
+        Ok(Box::new(WebDavBackend::new(settings)?))
+    } else {
+        Ok(Box::new(FolderBackend {
+            dir: PathBuf::from(target),
+        }))
+    }
+}
+
+// 读取同步目录中的盐, 不存在时生成一个
+fn load_salt(backend: &dyn Backend) -> Result<Vec<u8>> {
+    if backend.read_salt()?.is_none() {
+        let mut salt = [0u8; 16];
+        OsRng.fill_bytes(&mut salt);
+        backend.create_salt(&STANDARD.encode(salt))?;
+    }
+    // 重新读取, 其他设备可能同时写入了不同的盐
+    let salt = backend
+        .read_salt()?
+        .ok_or_else(|| Error::Sync("Failed to create sync salt".to_string()))?;
+    Ok(STANDARD.decode(salt.trim())?)
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<Aes256Gcm> {
+    if passphrase.is_empty() {
+        return Err(Error::Sync("Sync passphrase not configured".to_string()));
+    }
+
+    let mut cache = KEY_CACHE.lock().unwrap();
+    let cipher = match cache.as_ref() {
+        Some((cached_passphrase, cached_salt, key))
+            if cached_passphrase == passphrase && cached_salt == salt =>
+        {
+            key.clone()
+        }
+        _ => {
+            let mut key = [0u8; 32];
+            pbkdf2::pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, KDF_ROUNDS, &mut key);
+            let key = Aes256Gcm::new_from_slice(&key).map_err(|e| Error::Sync(e.to_string()))?;
+            *cache = Some((passphrase.to_string(), salt.to_vec(), key.clone()));
+            key
+        }
+    };
+    Ok(cipher)
+}
+
+// 日志中每行为 `2:` + base64(nonce + 密文)
+fn encrypt_entry(cipher: &Aes256Gcm, entry: &ChangeEntry) -> Result<String> {
+    let plaintext = serde_json::to_vec(entry)?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher
+        .encrypt(&nonce, plaintext.as_slice())
+        .map_err(|_| Error::Sync("Failed to encrypt sync entry".to_string()))?;
+
+    let mut data = nonce.to_vec();
+    data.extend_from_slice(&ciphertext);
+    Ok(format!("{}{}", LINE_PREFIX, STANDARD.encode(data)))
+}
+
+fn decrypt_entry(cipher: &Aes256Gcm, line: &str) -> Result<ChangeEntry> {
+    let line = line
+        .strip_prefix(LINE_PREFIX)
+        .ok_or_else(|| Error::Sync("Unsupported sync entry format".to_string()))?;
+    let data = STANDARD.decode(line)?;
+    if data.len() < 12 {
+        return Err(Error::Sync("Invalid sync entry".to_string()));
+    }
+    let (nonce, ciphertext) = data.split_at(12);
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| {
+            Error::Sync("Failed to decrypt sync entry, check the passphrase".to_string())
+        })?;
+    Ok(serde_json::from_slice(&plaintext)?)
+}
+
+// 两次推送之间同一条记录的多次变更合并成一条
+struct PendingChange {
+    record_id: i64,
+    op: ChangeOp,
+    record_type: Option<String>,
+    // 删除时为被删除内容的哈希
+    hash: Option<String>,
+    // 其他设备已知的内容哈希, 即这批变更中第一次修改前的哈希
+    old_hash: Option<String>,
+    changed_at: String,
+}
+
+fn read_pending_changes(
+    conn: &rusqlite::Connection,
+) -> Result<Vec<PendingChange>, rusqlite::Error> {
+    let mut stmt = conn.prepare(
+        "SELECT record_id, op, record_type, old_hash, changed_at
+         FROM record_change ORDER BY seq",
+    )?;
+    let rows = stmt
+        .query_map([], |row| {
+            let op = if row.get::<_, String>(1)? == "delete" {
+                ChangeOp::Delete
+            } else {
+                ChangeOp::Upsert
+            };
+            let old_hash: Option<String> = row.get(3)?;
+            let (hash, old_hash) = match op {
+                ChangeOp::Delete => (old_hash, None),
+                ChangeOp::Upsert => (None, old_hash),
+            };
+            Ok(PendingChange {
+                record_id: row.get(0)?,
+                op,
+                record_type: row.get(2)?,
+                hash,
+                old_hash,
+                changed_at: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut changes: Vec<PendingChange> = Vec::new();
+    let mut latest: HashMap<i64, usize> = HashMap::new();
+    for change in rows {
+        // 删除之后的变更单独保留, 先删除再恢复时其他设备按顺序处理
+        if let Some(&i) = latest.get(&change.record_id) {
+            let prev = &mut changes[i];
+            if prev.op == ChangeOp::Upsert {
+                prev.op = change.op;
+                prev.record_type = change.record_type;
+                prev.hash = change.hash;
+                prev.old_hash = prev.old_hash.take().or(change.old_hash);
+                prev.changed_at = change.changed_at;
+                continue;
+            }
+        }
+        latest.insert(change.record_id, changes.len());
+        changes.push(change);
+    }
+    Ok(changes)
+}
+
+fn delete_entry(record_type: &str, hash: &str, changed_at: &str) -> ChangeEntry {
+    ChangeEntry {
+        op: ChangeOp::Delete,
+        record_type: record_type.to_string(),
+        value: String::new(),
+        hash: Some(hash.to_string()),
+        size: None,
+        img_size: None,
+        favorite: false,
+        favorite_at: None,
+        created_at: String::new(),
+        updated_at: changed_at.to_string(),
+        changed_at: changed_at.to_string(),
+    }
+}
+
+// 把本机待同步的变更追加到自己的日志, 返回推送的条数, 会过期的临时记录不同步
+fn push(
+    db: &Database,
+    backend: &dyn Backend,
+    cipher: &Aes256Gcm,
+    device_id: &str,
+) -> Result<usize> {
+    let (max_seq, entries) = db.write(|conn| {
+        let max_seq: Option<i64> =
+            conn.query_row("SELECT MAX(seq) FROM record_change", [], |row| row.get(0))?;
+
+        let mut entries = Vec::new();
+        for change in read_pending_changes(conn)? {
+            let changed_at = change.changed_at;
+            if change.op == ChangeOp::Delete {
+                let (Some(record_type), Some(hash)) = (&change.record_type, &change.hash) else {
+                    continue;
+                };
+                entries.push(delete_entry(record_type, hash, &changed_at));
+                // 删除前在本机修改过内容, 其他设备上保存的还是修改前的内容
+                if let Some(old_hash) = change.old_hash.filter(|h| h != hash) {
+                    entries.push(delete_entry(record_type, &old_hash, &changed_at));
+                }
+                continue;
+            }
+
+            let entry = conn
+                .query_row(
+                    "SELECT record_type, value, size, img_size, favorite, favorite_at,
+                            created_at, updated_at
+                     FROM record
+                     WHERE id = ?1 AND deleted_at IS NULL AND expires_at IS NULL",
+                    [change.record_id],
+                    |row| {
+                        Ok(ChangeEntry {
+                            op: ChangeOp::Upsert,
+                            record_type: row.get(0)?,
+                            value: row.get(1)?,
+                            hash: change.old_hash.clone(),
+                            size: row.get(2)?,
+                            img_size: row.get(3)?,
+                            favorite: row.get::<_, i64>(4)? != 0,
+                            favorite_at: row.get(5)?,
+                            created_at: row.get(6)?,
+                            updated_at: row.get(7)?,
+                            changed_at: changed_at.clone(),
+                        })
+                    },
+                )
+                .optional()?;
+            entries.extend(entry);
+        }
+        Ok((max_seq, entries))
+    })?;
+
+    let Some(max_seq) = max_seq else {
+        return Ok(0);
+    };
+
+    if !entries.is_empty() {
+        let mut lines = String::new();
+        for entry in &entries {
+            lines.push_str(&encrypt_entry(cipher, entry)?);
+            lines.push('\n');
+        }
+        backend.append_log(device_id, &lines)?;
+    }
+
+    db.write(|conn| conn.execute("DELETE FROM record_change WHERE seq <= ?1", [max_seq]))?;
+
+    Ok(entries.len())
+}
+
+// 合并一条来自其他设备的变更, 不写入 record_change, 避免被再次推送
+// 各设备的操作按变更时间以后者为准
+fn merge_entry(
+    conn: &rusqlite::Connection,
+    entry: &ChangeEntry,
+    optimized: Option<&OptimizedImg>,
+) -> Result<(), rusqlite::Error> {
+    let changed_at = &entry.changed_at;
+
+    if entry.op == ChangeOp::Delete {
+        // 删除之后在本机又复制过的记录保留
+        if let Some(hash) = &entry.hash {
+            conn.execute(
+                "UPDATE record SET deleted_at = ?3
+                 WHERE record_type = ?1 AND hash = ?2 AND deleted_at IS NULL AND updated_at <= ?3",
+                (&entry.record_type, hash, changed_at),
+            )?;
+        }
+        return Ok(());
+    }
+
+    let hash = db::content_hash(&entry.value);
+    let classification = (entry.record_type == "text").then(|| classify(&entry.value));
+    let existing_id = db::check_record_exists(conn, &entry.record_type, &hash)?;
+    // 内容在其他设备上被修改过, 找到修改前的记录
+    let replaced_id = match &entry.hash {
+        Some(old_hash) if *old_hash != hash => {
+            db::check_record_exists(conn, &entry.record_type, old_hash)?
+        }
+        _ => None,
+    };
+
+    let mut id = existing_id;
+    if let Some(existing_id) = existing_id {
+        // 在其他设备上恢复或重新复制了本机已删除的记录
+        conn.execute(
+            "UPDATE record SET updated_at = MAX(updated_at, ?1),
+                 deleted_at = CASE WHEN deleted_at < ?2 THEN NULL ELSE deleted_at END
+             WHERE id = ?3",
+            (&entry.updated_at, changed_at, existing_id),
+        )?;
+        if let Some(replaced_id) = replaced_id {
+            conn.execute(
+                "UPDATE record SET deleted_at = ?1
+                 WHERE id = ?2 AND deleted_at IS NULL AND updated_at <= ?1",
+                (changed_at, replaced_id),
+            )?;
+        }
+    } else if let Some(replaced_id) = replaced_id {
+        // 修改之后在本机又复制过原来的内容时保留原记录, 修改后的内容作为新记录
+        conn.execute(
+            "INSERT INTO record_revision (record_id, value)
+             SELECT id, value FROM record WHERE id = ?1 AND updated_at <= ?2",
+            (replaced_id, changed_at),
+        )?;
+        let edited = conn.execute(
+            "UPDATE record SET value = ?1, hash = ?2, subtype = ?3, language = ?4, size = ?5,
+                 highlight_html = NULL, updated_at = ?6, deleted_at = NULL
+             WHERE id = ?7 AND updated_at <= ?8",
+            (
+                &entry.value,
+                &hash,
+                classification.map(|c| c.subtype),
+                classification.and_then(|c| c.language),
+                entry.size,
+                &entry.updated_at,
+                replaced_id,
+                changed_at,
+            ),
+        )?;
+        if edited > 0 {
+            id = Some(replaced_id);
+        }
+    }
+
+    if let Some(id) = id {
+        // 没有修改过收藏的记录没有收藏时间, 不覆盖本机的收藏状态
+        conn.execute(
+            "UPDATE record SET favorite = ?1, favorite_at = ?2
+             WHERE id = ?3 AND ?2 IS NOT NULL AND (favorite_at IS NULL OR favorite_at < ?2)",
+            (entry.favorite, &entry.favorite_at, id),
+        )?;
+        return Ok(());
+    }
+
+    conn.execute(
+        "INSERT INTO record (record_type, value, hash, thumbnail_key, preview_key, phash, subtype, language, size, img_size, favorite, favorite_at, created_at, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)",
+        (
+            &entry.record_type,
+            &entry.value,
+            &hash,
+            optimized.map(|o| &o.thumbnail_key),
+            optimized.map(|o| &o.preview_key),
+            optimized.map(|o| o.phash as i64),
+            classification.map(|c| c.subtype),
+            classification.and_then(|c| c.language),
+            entry.size,
+            &entry.img_size,
+            entry.favorite,
+            &entry.favorite_at,
+            &entry.created_at,
+            &entry.updated_at,
+        ),
+    )?;
+    Ok(())
+}
+
+// 读取其他设备的日志, 合并新增的部分, 单个设备失败时记录下来继续处理其他设备
+fn pull(
+    db: &Database,
+    backend: &dyn Backend,
+    cipher: &Aes256Gcm,
+    device_id: &str,
+) -> Result<PullReport> {
+    let mut report = PullReport::default();
+
+    for peer in backend.list_devices()? {
+        if peer == device_id {
+            continue;
+        }
+
+        match pull_device(db, backend, cipher, &peer) {
+            Ok((merged, skipped)) => {
+                report.merged += merged;
+                report.skipped += skipped;
+                if skipped > 0 {
+                    report.warnings.push(format!(
+                        "{}: skipped {} entries that failed to decrypt",
+                        peer, skipped
+                    ));
+                }
+            }
+            Err(e) => {
+                tracing::warn!(peer, error = %e, "failed to pull sync log");
+                report.warnings.push(format!("{}: {}", peer, e));
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+// 合并一个设备的日志, 返回合并和跳过的条数
+fn pull_device(
+    db: &Database,
+    backend: &dyn Backend,
+    cipher: &Aes256Gcm,
+    peer: &str,
+) -> Result<(usize, usize)> {
+    let content = backend.read_log(peer)?;
+    let lines: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+
+    let cursor: Option<(i64, Option<String>)> = db.read(|conn| {
+        conn.query_row(
+            "SELECT position, last_line FROM sync_cursor WHERE device_id = ?1",
+            [peer],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()
+    })?;
+    let (position, last_line) = cursor.unwrap_or((0, None));
+    let mut position = position.max(0) as usize;
+
+    // 日志被清空或重建后, 原来的位置对应的不再是合并过的行, 从头重新合并, 重复合并不影响结果
+    if position > lines.len() || (position > 0 && last_line.as_deref() != Some(lines[position - 1]))
+    {
+        tracing::warn!(
+            peer,
+            position,
+            lines = lines.len(),
+            "sync log was rewritten, merging it again"
+        );
+        position = 0;
+    }
+    if position == lines.len() {
+        return Ok((0, 0));
+    }
+
+    // 损坏的行跳过, 不影响之后的同步
+    let mut entries = Vec::new();
+    let mut skipped = 0;
+    for (i, line) in lines.iter().enumerate().skip(position) {
+        match decrypt_entry(cipher, line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                tracing::warn!(peer, line = i + 1, error = %e, "skipped sync entry");
+                skipped += 1;
+            }
+        }
+    }
+    // 所有新行都无法解密时多半是口令不一致, 不移动位置, 修改口令后可以重新合并
+    if entries.is_empty() {
+        return Err(Error::Sync(
+            "Failed to decrypt sync entries, check the passphrase".to_string(),
+        ));
+    }
+
+    // 缩略图只保存在本机的 blob store 中, 不随日志同步, 在加锁写库之前重新生成
+    let optimized: Vec<Option<OptimizedImg>> = entries
+        .iter()
+        .map(|entry| {
+            if entry.op != ChangeOp::Upsert || entry.record_type != "image" {
+                return None;
+            }
+            STANDARD
+                .decode(&entry.value)
+                .map_err(Error::from)
+                .and_then(|bytes| optimize_img(db.blobs(), &bytes))
+                .ok()
+        })
+        .collect();
+
+    db.write(|conn| {
+        let tx = conn.unchecked_transaction()?;
+        for (entry, optimized) in entries.iter().zip(&optimized) {
+            merge_entry(&tx, entry, optimized.as_ref())?;
+        }
+        tx.execute(
+            "INSERT OR REPLACE INTO sync_cursor (device_id, position, last_line)
+             VALUES (?1, ?2, ?3)",
+            (peer, lines.len() as i64, lines.last()),
+        )?;
+        tx.commit()
+    })?;
+
+    Ok((entries.len(), skipped))
+}
+
+fn sync_once(db: &Database) -> Result<(usize, PullReport)> {
+    let _guard = SYNC_LOCK.lock().unwrap();
+
+    let settings = settings::get();
+    let backend = open_backend(&settings.sync)?;
+    let salt = load_salt(backend.as_ref())?;
+    let cipher = cipher(&settings.sync.passphrase, &salt)?;
+
+    let pushed = push(db, backend.as_ref(), &cipher, &settings.device_id)?;
+    let report = pull(db, backend.as_ref(), &cipher, &settings.device_id)?;
+    Ok((pushed, report))
+}
+
+fn emit_status(app: &AppHandle, status: SyncStatus) {
+    let _ = app.emit("sync-status", status);
+}
+
+// 执行一次同步, 并通过 sync-status 事件通知前端
+fn run(app: &AppHandle) -> Result<SyncStatus> {
+    emit_status(
+        app,
+        SyncStatus {
+            state: "syncing".to_string(),
+            pushed: 0,
+            merged: 0,
+            skipped: 0,
+            message: None,
+        },
+    );
+
+    match sync_once(app.state::<Database>().inner()) {
+        Ok((pushed, report)) => {
+            tracing::info!(
+                pushed,
+                merged = report.merged,
+                skipped = report.skipped,
+                "sync finished"
+            );
+            // 部分设备的日志无法合并时仍然算同步完成, 通过 message 提示
+            let status = SyncStatus {
+                state: "idle".to_string(),
+                pushed,
+                merged: report.merged,
+                skipped: report.skipped,
+                message: (!report.warnings.is_empty()).then(|| report.warnings.join("; ")),
+            };
+            emit_status(app, status.clone());
+            Ok(status)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "sync failed");
+            emit_status(
+                app,
+                SyncStatus {
+                    state: "error".to_string(),
+                    pushed: 0,
+                    merged: 0,
+                    skipped: 0,
+                    message: Some(e.to_string()),
+                },
+            );
+            Err(e)
+        }
+    }
+}
+
+pub fn init(app: &tauri::App) {
+    let app_handle = app.app_handle().clone();
+    // 按设置的间隔定时同步
+    std::thread::spawn(move || loop {
+        let sync_settings = settings::get().sync;
+        if sync_settings.enabled {
+            let _ = run(&app_handle);
+        }
+        std::thread::sleep(Duration::from_secs(sync_settings.interval_secs.max(10)));
+    });
+}
+
+#[tauri::command]
+pub async fn sync_now(app: AppHandle) -> Result<SyncStatus> {
+    tauri::async_runtime::spawn_blocking(move || run(&app))
+        .await
+        .map_err(|e| Error::Other(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::db::RecordInput;
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+
+    struct Device {
+        _dir: tempfile::TempDir,
+        db: Database,
+        id: String,
+    }
+
+    fn device(id: &str) -> Device {
+        let dir = tempfile::tempdir().unwrap();
+        let db = db::init_at(dir.path()).unwrap();
+        Device {
+            _dir: dir,
+            db,
+            id: id.to_string(),
+        }
+    }
+
+    fn folder() -> (tempfile::TempDir, FolderBackend) {
+        let dir = tempfile::tempdir().unwrap();
+        let backend = FolderBackend {
+            dir: dir.path().join("sync"),
+        };
+        (dir, backend)
+    }
+
+    fn sync(device: &Device, backend: &dyn Backend, passphrase: &str) -> (usize, PullReport) {
+        let salt = load_salt(backend).unwrap();
+        let cipher = cipher(passphrase, &salt).unwrap();
+        let pushed = push(&device.db, backend, &cipher, &device.id).unwrap();
+        let report = pull(&device.db, backend, &cipher, &device.id).unwrap();
+        (pushed, report)
+    }
+
+    // 未删除的记录的 (内容, 是否收藏), 按内容排序
+    fn records(device: &Device) -> Vec<(String, bool)> {
+        device
+            .db
+            .read(|conn| {
+                let mut stmt = conn.prepare(
+                    "SELECT value, favorite FROM record WHERE deleted_at IS NULL ORDER BY value",
+                )?;
+                let rows = stmt
+                    .query_map([], |row| Ok((row.get(0)?, row.get::<_, i64>(1)? != 0)))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .unwrap()
+    }
+
+    fn id_of(device: &Device, value: &str) -> i64 {
+        device
+            .db
+            .read(|conn| db::check_record_exists(conn, "text", &db::content_hash(value)))
+            .unwrap()
+            .unwrap()
+    }
+
+    fn pending(device: &Device) -> i64 {
+        device
+            .db
+            .read(|conn| conn.query_row("SELECT COUNT(*) FROM record_change", [], |row| row.get(0)))
+            .unwrap()
+    }
+
+    fn owned(values: &[(&str, bool)]) -> Vec<(String, bool)> {
+        values.iter().map(|(v, f)| (v.to_string(), *f)).collect()
+    }
+
+    // 变更时间精确到毫秒, 保证先后两次操作的时间不同
+    fn tick() {
+        std::thread::sleep(Duration::from_millis(5));
+    }
+
+    #[test]
+    fn records_round_trip() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        a.db.add_record(RecordInput::text("hello")).unwrap();
+        let id = a.db.add_record(RecordInput::text("world")).unwrap();
+        a.db.toggle_favorite(id).unwrap();
+
+        let (pushed, _) = sync(&a, &backend, PASSPHRASE);
+        assert_eq!(pushed, 2);
+        assert_eq!(pending(&a), 0);
+
+        let (pushed, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!(pushed, 0);
+        assert_eq!(report.merged, 2);
+        assert!(report.warnings.is_empty());
+        assert_eq!(records(&b), owned(&[("hello", false), ("world", true)]));
+
+        // 合并进来的记录不会被再次推送, 重复同步不会重复合并
+        assert_eq!(pending(&b), 0);
+        let (pushed, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!((pushed, report.merged), (0, 0));
+    }
+
+    #[test]
+    fn log_is_encrypted() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        a.db.add_record(RecordInput::text("secret value")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+
+        let log = backend.read_log("a").unwrap();
+        assert_eq!(log.lines().count(), 1);
+        assert!(log.starts_with(LINE_PREFIX));
+        assert!(!log.contains("secret value"));
+    }
+
+    #[test]
+    fn salt_is_created_once() {
+        let (_dir, backend) = folder();
+        let salt = load_salt(&backend).unwrap();
+        assert_eq!(salt.len(), 16);
+        assert_eq!(load_salt(&backend).unwrap(), salt);
+
+        // 已经存在的盐不会被覆盖
+        backend.create_salt(&STANDARD.encode([0u8; 16])).unwrap();
+        assert_eq!(load_salt(&backend).unwrap(), salt);
+
+        // 不同的同步目录使用不同的盐, 派生出不同的密钥
+        let (_other_dir, other) = folder();
+        assert_ne!(load_salt(&other).unwrap(), salt);
+    }
+
+    #[test]
+    fn deletes_propagate() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        let deleted = a.db.add_record(RecordInput::text("deleted")).unwrap();
+        a.db.add_record(RecordInput::text("kept")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&b).len(), 2);
+
+        tick();
+        a.db.soft_delete(&[deleted], false).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&b), owned(&[("kept", false)]));
+
+        // 恢复也会同步
+        tick();
+        a.db.restore_records(&[deleted]).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&b), owned(&[("deleted", false), ("kept", false)]));
+
+        // 清空历史只保留最新的一条
+        tick();
+        a.db.clear_history().unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&a), records(&b));
+        assert_eq!(records(&b).len(), 1);
+    }
+
+    #[test]
+    fn delete_does_not_remove_newer_copy() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        let id = a.db.add_record(RecordInput::text("copied again")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+
+        tick();
+        a.db.soft_delete(&[id], false).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+
+        // 删除合并之前在本机又复制了一次
+        tick();
+        b.db.add_record(RecordInput::text("copied again")).unwrap();
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&b), owned(&[("copied again", false)]));
+    }
+
+    #[test]
+    fn edits_propagate() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        let id = a.db.add_record(RecordInput::text("before")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        let b_id = id_of(&b, "before");
+
+        tick();
+        a.db.update_record(id, "after".to_string()).unwrap();
+        tick();
+        a.db.update_record(id, "after again".to_string()).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+
+        // 修改的是同一条记录, 原来的内容进入修订历史
+        assert_eq!(records(&b), owned(&[("after again", false)]));
+        assert_eq!(id_of(&b, "after again"), b_id);
+        let revisions: Vec<String> =
+            b.db.read(|conn| {
+                let mut stmt =
+                    conn.prepare("SELECT value FROM record_revision WHERE record_id = ?1")?;
+                let rows = stmt
+                    .query_map([b_id], |row| row.get(0))?
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(rows)
+            })
+            .unwrap();
+        assert_eq!(revisions, vec!["before".to_string()]);
+    }
+
+    #[test]
+    fn edit_then_delete_removes_original() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        let id = a.db.add_record(RecordInput::text("original")).unwrap();
+        a.db.add_record(RecordInput::text("other")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+
+        tick();
+        a.db.update_record(id, "edited".to_string()).unwrap();
+        a.db.soft_delete(&[id], false).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        assert_eq!(records(&b), owned(&[("other", false)]));
+    }
+
+    #[test]
+    fn favorite_uses_last_writer() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        let a_id = a.db.add_record(RecordInput::text("shared")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        let b_id = id_of(&b, "shared");
+
+        // a 先收藏, b 之后收藏再取消, 以 b 的操作为准
+        tick();
+        a.db.toggle_favorite(a_id).unwrap();
+        tick();
+        b.db.toggle_favorite(b_id).unwrap();
+        tick();
+        b.db.toggle_favorite(b_id).unwrap();
+
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+        sync(&a, &backend, PASSPHRASE);
+        assert_eq!(records(&a), owned(&[("shared", false)]));
+        assert_eq!(records(&b), owned(&[("shared", false)]));
+
+        // 重新复制不会覆盖其他设备的收藏
+        tick();
+        a.db.toggle_favorite(a_id).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        tick();
+        b.db.add_record(RecordInput::text("shared")).unwrap();
+        sync(&b, &backend, PASSPHRASE);
+        sync(&a, &backend, PASSPHRASE);
+        assert_eq!(records(&a), owned(&[("shared", true)]));
+        assert_eq!(records(&b), owned(&[("shared", true)]));
+    }
+
+    #[test]
+    fn corrupt_line_is_skipped() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        a.db.add_record(RecordInput::text("first")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        backend.append_log("a", "2:not-base64\n").unwrap();
+        a.db.add_record(RecordInput::text("second")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!((report.merged, report.skipped), (2, 1));
+        assert_eq!(report.warnings.len(), 1);
+        assert_eq!(records(&b), owned(&[("first", false), ("second", false)]));
+
+        // 跳过的行不会在之后的同步中再次报告
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!((report.merged, report.skipped), (0, 0));
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn wrong_passphrase_does_not_block_other_devices() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+        let c = device("c");
+
+        a.db.add_record(RecordInput::text("from a")).unwrap();
+        sync(&a, &backend, "another passphrase");
+        b.db.add_record(RecordInput::text("from b")).unwrap();
+        sync(&b, &backend, PASSPHRASE);
+
+        let (_, report) = sync(&c, &backend, PASSPHRASE);
+        assert_eq!(report.merged, 1);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].starts_with("a: "));
+        assert_eq!(records(&c), owned(&[("from b", false)]));
+
+        // 口令不一致时不移动位置, 改成一致的口令后可以合并
+        let (_, report) = sync(&c, &backend, "another passphrase");
+        assert_eq!(report.merged, 1);
+        assert_eq!(records(&c), owned(&[("from a", false), ("from b", false)]));
+    }
+
+    #[test]
+    fn rewritten_log_is_merged_again() {
+        let (_dir, backend) = folder();
+        let a = device("a");
+        let b = device("b");
+
+        a.db.add_record(RecordInput::text("first")).unwrap();
+        a.db.add_record(RecordInput::text("second")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        sync(&b, &backend, PASSPHRASE);
+
+        // a 的日志被删除后重新开始写入, 行数比 b 记录的位置少
+        std::fs::remove_file(backend.dir.join("a.log")).unwrap();
+        a.db.add_record(RecordInput::text("third")).unwrap();
+        sync(&a, &backend, PASSPHRASE);
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!(report.merged, 1);
+        assert_eq!(
+            records(&b),
+            owned(&[("first", false), ("second", false), ("third", false)])
+        );
+
+        // 重建后的日志长度超过原来的位置时同样能发现
+        std::fs::remove_file(backend.dir.join("a.log")).unwrap();
+        for value in ["fourth", "fifth", "sixth"] {
+            a.db.add_record(RecordInput::text(value)).unwrap();
+        }
+        sync(&a, &backend, PASSPHRASE);
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!(report.merged, 3);
+        assert_eq!(records(&b).len(), 6);
+
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!(report.merged, 0);
+    }
+
+    // 内存中的 WebDAV 服务, 只实现同步用到的方法
+    fn serve_webdav() -> String {
+        use std::collections::HashMap;
+        use std::io::{BufRead, BufReader, Read};
+        use std::net::TcpListener;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let mut files: HashMap<String, Vec<u8>> = HashMap::new();
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                let mut headers = HashMap::new();
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    if line == "\r\n" || line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(':') {
+                        headers.insert(name.trim().to_lowercase(), value.trim().to_string());
+                    }
+                }
+                let length: usize = headers
+                    .get("content-length")
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(0);
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let mut parts = request_line.split_whitespace();
+                let method = parts.next().unwrap_or_default();
+                let path = parts.next().unwrap_or_default().to_string();
+                // user:secret
+                let authorized = headers.get("authorization").map(String::as_str)
+                    == Some("Basic dXNlcjpzZWNyZXQ=");
+                let (status, body) = if !authorized {
+                    ("401 Unauthorized", Vec::new())
+                } else {
+                    match method {
+                        "MKCOL" => ("405 Method Not Allowed", Vec::new()),
+                        "GET" => match files.get(&path) {
+                            Some(content) => ("200 OK", content.clone()),
+                            None => ("404 Not Found", Vec::new()),
+                        },
+                        "PUT" => {
+                            let exists = files.contains_key(&path);
+                            if exists
+                                && headers.get("if-none-match").map(String::as_str) == Some("*")
+                            {
+                                ("412 Precondition Failed", Vec::new())
+                            } else {
+                                files.insert(path, body);
+                                ("201 Created", Vec::new())
+                            }
+                        }
+                        "PROPFIND" => {
+                            let mut xml = String::from("<d:multistatus xmlns:d=\"DAV:\">");
+                            xml.push_str(&format!(
+                                "<d:response><d:href>{}</d:href></d:response>",
+                                path
+                            ));
+                            for name in files.keys() {
+                                xml.push_str(&format!(
+                                    "<d:response><d:href>{}</d:href></d:response>",
+                                    name
+                                ));
+                            }
+                            xml.push_str("</d:multistatus>");
+                            ("207 Multi-Status", xml.into_bytes())
+                        }
+                        _ => ("405 Method Not Allowed", Vec::new()),
+                    }
+                };
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    status,
+                    body.len()
+                );
+                let _ = stream.write_all(&body);
+            }
+        });
+        format!("http://127.0.0.1:{}/clippy", port)
+    }
+
+    fn webdav(target: &str, password: &str) -> WebDavBackend {
+        WebDavBackend::new(&SyncSettings {
+            target: target.to_string(),
+            username: Some("user".to_string()),
+            password: Some(password.to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn webdav_round_trip() {
+        let target = serve_webdav();
+        let backend = webdav(&target, "secret");
+        let a = device("a");
+        let b = device("b");
+
+        assert!(backend.list_devices().unwrap().is_empty());
+        let id = a.db.add_record(RecordInput::text("over webdav")).unwrap();
+        a.db.toggle_favorite(id).unwrap();
+        assert_eq!(sync(&a, &backend, PASSPHRASE).0, 1);
+        a.db.add_record(RecordInput::text("appended")).unwrap();
+        assert_eq!(sync(&a, &backend, PASSPHRASE).0, 1);
+        assert_eq!(backend.list_devices().unwrap(), vec!["a".to_string()]);
+        assert_eq!(backend.read_log("a").unwrap().lines().count(), 2);
+
+        // 盐只写入一次
+        let salt = backend.read_salt().unwrap().unwrap();
+        backend.create_salt("other").unwrap();
+        assert_eq!(backend.read_salt().unwrap().unwrap(), salt);
+
+        let (_, report) = sync(&b, &backend, PASSPHRASE);
+        assert_eq!(report.merged, 2);
+        assert_eq!(
+            records(&b),
+            owned(&[("appended", false), ("over webdav", true)])
+        );
+    }
+
+    #[test]
+    fn webdav_errors_are_reported() {
+        let target = serve_webdav();
+        let backend = webdav(&target, "wrong");
+        assert!(backend.list_devices().is_err());
+        assert!(backend.read_log("a").is_err());
+        assert!(backend.append_log("a", "line\n").is_err());
+        assert!(backend.create_salt("salt").is_err());
+    }
+
+    #[test]
+    fn propfind_devices_are_parsed() {
+        let body = r#"<d:multistatus xmlns:d="DAV:">
+            <d:response><d:href>/dav/clippy/</d:href></d:response>
+            <d:response><d:href>/dav/clippy/abc.log</d:href></d:response>
+            <d:response><d:href>/dav/clippy/salt</d:href></d:response>
+            <D:response><D:href> https://host/dav/clippy/def.log </D:href></D:response>
+            <d:response><d:href>/dav/clippy/abc.log</d:href></d:response>
+        </d:multistatus>"#;
+        assert_eq!(
+            parse_propfind_devices(body),
+            vec!["abc".to_string(), "def".to_string()]
+        );
+    }
+}
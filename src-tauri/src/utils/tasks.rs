@@ -0,0 +1,47 @@
+use std::sync::mpsc::Sender;
+
+use crate::utils::db::Database;
+use crate::utils::{highlight, link_preview, ocr};
+
+// 记录入库后的后台任务(文字识别、链接预览、语法高亮)的队列
+// 在 setup 中启动后台线程后创建, 传给写入记录的模块, 同时作为 tauri 的 managed state
+// 后台线程已经退出时任务直接丢弃
+#[derive(Clone)]
+pub struct Tasks {
+    ocr: Sender<i64>,
+    link_preview: Sender<String>,
+    highlight: Sender<i64>,
+}
+
+impl Tasks {
+    pub fn start(db: &Database) -> Tasks {
+        Tasks {
+            ocr: ocr::init(db.clone()),
+            link_preview: link_preview::init(db.clone()),
+            highlight: highlight::init(db.clone()),
+        }
+    }
+
+    // 识别图片记录中的文字
+    pub fn recognize_text(&self, id: i64) {
+        let _ = self.ocr.send(id);
+    }
+
+    // 文本记录是链接时抓取预览, 是代码时生成高亮
+    pub fn text_added(&self, id: i64, text: &str) {
+        if link_preview::should_fetch(text) {
+            let _ = self.link_preview.send(text.trim().to_string());
+        }
+        let _ = self.highlight.send(id);
+    }
+
+    // 测试中不启动后台线程
+    #[cfg(test)]
+    pub fn detached() -> Tasks {
+        Tasks {
+            ocr: std::sync::mpsc::channel().0,
+            link_preview: std::sync::mpsc::channel().0,
+            highlight: std::sync::mpsc::channel().0,
+        }
+    }
+}
@@ -1,58 +1,134 @@
-use crate::utils::{db, nspanel};
+use crate::utils::db::Database;
+use crate::utils::error::Result;
+use crate::utils::pause::{self, CaptureStatus};
+use crate::utils::{diagnostics, nspanel};
 use tauri::path::BaseDirectory;
 use tauri::{
     image,
-    menu::{AboutMetadata, Menu, MenuItem, PredefinedMenuItem},
+    menu::{AboutMetadata, IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
     tray::TrayIconBuilder,
 };
-use tauri::{Emitter, Manager};
+use tauri::{AppHandle, Emitter, Manager, Wry};
 use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind};
 
-pub fn init(app: &tauri::App) {
-    let separator = PredefinedMenuItem::separator(app).unwrap();
-    let open = MenuItem::with_id(app, "open", "打开/隐藏面板", true, Some("Cmd+Shift+V")).unwrap();
-    let clear_history =
-        MenuItem::with_id(app, "clear_history", "清理历史记录", true, None::<&str>).unwrap();
-    let about =
-        PredefinedMenuItem::about(app, Some("关于clippy2"), Some(generate_metadata(&app))).unwrap();
-    let quit = PredefinedMenuItem::quit(app, Some("退出clippy2")).unwrap();
-    let menu = Menu::with_items(app, &[&open, &clear_history, &separator, &about, &quit]).unwrap();
-
-    let icon = image::Image::from_path(
-        app.path()
-            .resolve("assets/tray.png", BaseDirectory::Resource)
-            .unwrap(),
-    )
-    .unwrap();
+const TRAY_ID: &str = "main";
+const TOOLTIP: &str = "Cmd+Shift+V 打开";
+const PAUSED_TOOLTIP: &str = "已暂停记录剪贴板, Cmd+Ctrl+V 恢复";
 
-    TrayIconBuilder::new()
-        .icon(icon)
-        .menu(&menu)
+pub fn init(app: &tauri::App) -> Result<()> {
+    let app_handle = app.handle();
+    TrayIconBuilder::with_id(TRAY_ID)
+        .icon(tray_icon(app_handle, false)?)
+        .menu(&build_menu(app_handle, false)?)
         .show_menu_on_left_click(true)
         .on_menu_event(|app, event| match event.id.as_ref() {
             "open" => {
-                println!("open panel by tray");
-                nspanel::toggle_panel(app.clone());
+                tracing::debug!("open panel by tray");
+                if let Err(e) = nspanel::toggle_panel(app.clone()) {
+                    tracing::error!(error = %e, "failed to toggle panel");
+                }
+            }
+            "pause_15" | "pause_60" | "pause" => {
+                let minutes = match event.id.as_ref() {
+                    "pause_15" => Some(15),
+                    "pause_60" => Some(60),
+                    _ => None,
+                };
+                if let Err(e) = pause::pause(app, minutes) {
+                    tracing::error!(error = %e, "failed to pause capture");
+                }
+            }
+            "resume" => {
+                pause::resume(app);
             }
             "clear_history" => {
                 pre_clear_history(&app);
             }
+            "diagnostics" => {
+                create_diagnostics_bundle(&app);
+            }
             _ => {
-                println!("menu item {:?} not handled", event.id);
+                tracing::warn!(id = ?event.id, "menu item not handled");
             }
         })
-        .tooltip("Cmd+Shift+V 打开")
-        .build(app)
-        .unwrap();
+        .tooltip(TOOLTIP)
+        .build(app)?;
+    Ok(())
+}
+
+// 暂停状态变化后更新菜单、图标和提示
+pub fn refresh(app: &AppHandle, status: &CaptureStatus) -> Result<()> {
+    let Some(tray) = app.tray_by_id(TRAY_ID) else {
+        return Ok(());
+    };
+    tray.set_menu(Some(build_menu(app, status.paused)?))?;
+    tray.set_icon(Some(tray_icon(app, status.paused)?))?;
+    tray.set_tooltip(Some(if status.paused {
+        PAUSED_TOOLTIP
+    } else {
+        TOOLTIP
+    }))?;
+    Ok(())
+}
+
+fn build_menu(app: &AppHandle, paused: bool) -> Result<Menu<Wry>> {
+    let separator = PredefinedMenuItem::separator(app)?;
+    let open = MenuItem::with_id(app, "open", "打开/隐藏面板", true, Some("Cmd+Shift+V"))?;
+    // 暂停时只显示恢复, 否则显示暂停的时长选项
+    let resume = MenuItem::with_id(app, "resume", "恢复记录", true, Some("Cmd+Ctrl+V"))?;
+    let pause_menu = Submenu::with_items(
+        app,
+        "暂停记录",
+        true,
+        &[
+            &MenuItem::with_id(app, "pause_15", "15 分钟", true, None::<&str>)?,
+            &MenuItem::with_id(app, "pause_60", "1 小时", true, None::<&str>)?,
+            &MenuItem::with_id(app, "pause", "直到手动恢复", true, Some("Cmd+Ctrl+V"))?,
+        ],
+    )?;
+    let pause_item: &dyn IsMenuItem<Wry> = if paused { &resume } else { &pause_menu };
+    let clear_history =
+        MenuItem::with_id(app, "clear_history", "清理历史记录", true, None::<&str>)?;
+    let export_diagnostics =
+        MenuItem::with_id(app, "diagnostics", "导出诊断信息", true, None::<&str>)?;
+    let about = PredefinedMenuItem::about(app, Some("关于clippy2"), Some(generate_metadata(app)?))?;
+    let quit = PredefinedMenuItem::quit(app, Some("退出clippy2"))?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &open,
+            pause_item,
+            &clear_history,
+            &export_diagnostics,
+            &separator,
+            &about,
+            &quit,
+        ],
+    )?;
+    Ok(menu)
+}
+
+// 暂停时图标变淡
+fn tray_icon(app: &AppHandle, paused: bool) -> Result<image::Image<'static>> {
+    let icon = image::Image::from_path(
+        app.path()
+            .resolve("assets/tray.png", BaseDirectory::Resource)?,
+    )?;
+    if !paused {
+        return Ok(icon);
+    }
+    let mut rgba = icon.rgba().to_vec();
+    for pixel in rgba.chunks_exact_mut(4) {
+        pixel[3] = (pixel[3] as u16 * 2 / 5) as u8;
+    }
+    Ok(image::Image::new_owned(rgba, icon.width(), icon.height()))
 }
 
-fn generate_metadata(app: &tauri::App) -> AboutMetadata {
+fn generate_metadata(app: &AppHandle) -> Result<AboutMetadata<'static>> {
     let icon = image::Image::from_path(
         app.path()
-            .resolve("assets/icon.png", BaseDirectory::Resource)
-            .unwrap(),
-    )
-    .unwrap();
+            .resolve("assets/icon.png", BaseDirectory::Resource)?,
+    )?;
 
     let pkg_info = app.package_info();
     let metadata = AboutMetadata {
@@ -69,7 +145,7 @@ fn generate_metadata(app: &tauri::App) -> AboutMetadata {
         icon: Some(icon),
     };
 
-    metadata
+    Ok(metadata)
 }
 
 fn pre_clear_history(app: &tauri::AppHandle) {
@@ -81,30 +157,52 @@ fn pre_clear_history(app: &tauri::AppHandle) {
         .buttons(MessageDialogButtons::YesNo)
         .show(move |result| {
             if result {
-                tauri::async_runtime::block_on(async {
-                    match db::clear_history().await {
-                        Ok(_) => {
-                            // 通知渲染进程刷新数据
-                            app_handle
-                                .emit_to("main", "history-cleared", None::<&str>)
-                                .unwrap();
+                match app_handle.state::<Database>().clear_history() {
+                    Ok(_) => {
+                        // 通知渲染进程刷新数据
+                        let _ = app_handle.emit_to("main", "history-cleared", None::<&str>);
 
-                            app_handle
-                                .dialog()
-                                .message("历史记录已清理完成")
-                                .title("成功")
-                                .show(|_| {});
-                        }
-                        Err(e) => {
-                            app_handle
-                                .dialog()
-                                .message(format!("清理失败: {}", e))
-                                .kind(MessageDialogKind::Error)
-                                .title("错误")
-                                .show(|_| {});
-                        }
+                        app_handle
+                            .dialog()
+                            .message("历史记录已清理完成")
+                            .title("成功")
+                            .show(|_| {});
+                    }
+                    Err(e) => {
+                        app_handle
+                            .dialog()
+                            .message(format!("清理失败: {}", e))
+                            .kind(MessageDialogKind::Error)
+                            .title("错误")
+                            .show(|_| {});
                     }
-                });
+                }
             }
         });
 }
+
+fn create_diagnostics_bundle(app: &tauri::AppHandle) {
+    let app_handle = app.clone();
+
+    tauri::async_runtime::spawn(async move {
+        let db = app_handle.state::<Database>();
+        match diagnostics::create_diagnostics_bundle(app_handle.clone(), db).await {
+            Ok(path) => {
+                app_handle
+                    .dialog()
+                    .message(format!("诊断信息已保存到: {}", path))
+                    .title("成功")
+                    .show(|_| {});
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to create diagnostics bundle");
+                app_handle
+                    .dialog()
+                    .message(format!("导出失败: {}", e))
+                    .kind(MessageDialogKind::Error)
+                    .title("错误")
+                    .show(|_| {});
+            }
+        }
+    });
+}
@@ -18,7 +18,8 @@ function App() {
   const [selectedType, setSelectedType] = useState<
     RecordType | "all" | "favorite"
   >("all")
-  const [hasMore, setHasMore] = useState(true)
+  // 加载更多时使用的游标, 为空表示没有更多
+  const [nextCursor, setNextCursor] = useState<string>()
   const listRef = useRef<RecordListRef>(null)
 
   const handleSearch = useCallback(
@@ -32,14 +33,14 @@ function App() {
 
   const debouncedLoadRef = useRef(
     debounce(async (kw: string, type: RecordType | "all" | "favorite") => {
-      const data = await getRecords({
+      const { records: data, next_cursor } = await getRecords({
         limit: LIMIT,
         keyword: kw,
         record_type: type === "all" || type === "favorite" ? undefined : type,
         favorite: type === "favorite",
       })
       setRecords(data)
-      setHasMore(data.length === LIMIT)
+      setNextCursor(next_cursor)
       if (data.length > 0) {
         setSelectedId(data[0].id)
       }
@@ -52,7 +53,7 @@ function App() {
 
   const loadRecords = useCallback(async () => {
     // 检查是否有新的记录, 如果没有, 则不更新
-    const data = await getRecords({
+    const { records: data, next_cursor } = await getRecords({
       limit: LIMIT,
       keyword,
       record_type:
@@ -66,20 +67,17 @@ function App() {
     }
 
     setRecords(data)
-    setHasMore(data.length === LIMIT)
+    setNextCursor(next_cursor)
     if (data.length) {
       setSelectedId(data[0].id)
     }
   }, [keyword, records, selectedType])
 
   const loadMore = useCallback(async () => {
-    if (!hasMore) return
+    if (!nextCursor) return
 
-    const lastRecord = records[records.length - 1]
-    if (!lastRecord) return
-
-    const data = await getRecords({
-      last_updated_at: lastRecord.updated_at,
+    const { records: data, next_cursor } = await getRecords({
+      cursor: nextCursor,
       limit: LIMIT,
       keyword,
       record_type:
@@ -90,8 +88,8 @@ function App() {
     })
 
     setRecords((prev) => [...prev, ...data])
-    setHasMore(data.length === LIMIT)
-  }, [hasMore, keyword, records, selectedType])
+    setNextCursor(next_cursor)
+  }, [keyword, nextCursor, selectedType])
 
   useEffect(() => {
     function handleFocus() {
@@ -105,6 +103,25 @@ function App() {
     }
   }, [loadRecords])
 
+  useEffect(() => {
+    // 删除后从列表中移除, 撤销删除后重新加载
+    const unlistenDeleted = listen<{ ids: number[] }>(
+      "records-deleted",
+      (event) => {
+        const ids = event.payload.ids
+        setRecords((prev) => prev.filter((r) => !ids.includes(r.id)))
+      }
+    )
+    const unlistenRestored = listen("records-restored", () =>
+      debouncedLoadRef.current(keyword, selectedType)
+    )
+
+    return () => {
+      unlistenDeleted.then((f) => f())
+      unlistenRestored.then((f) => f())
+    }
+  }, [keyword, selectedType])
+
   const handleSelect = useCallback((record: Record) => {
     setSelectedId(record.id)
   }, [])
@@ -1,20 +1,59 @@
-import { useCallback, useRef } from "react"
-import { invoke } from "@tauri-apps/api/core"
+import { useCallback, useEffect, useRef, useState } from "react"
+import { convertFileSrc, invoke } from "@tauri-apps/api/core"
 import classNames from "classnames"
 
 import textIcon from "../../assets/text.png"
 import picIcon from "../../assets/pic.png"
 import fileIcon from "../../assets/file.png"
 import colorIcon from "../../assets/color.png"
-import { type Record } from "../../utils/db"
+import {
+  type LinkPreview,
+  type Record,
+  type RecordPreview,
+} from "../../utils/db"
 import styles from "./styles.module.scss"
 
+// 文本子类型对应的标题
+const SUBTYPE_TITLES: { [subtype: string]: string } = {
+  url: "链接",
+  email: "邮箱",
+  phone: "电话",
+  color: "颜色",
+  json: "JSON",
+  code: "代码",
+  path: "路径",
+  number: "数字",
+  date: "日期",
+}
+
 interface Props {
   record: Record
   setRecords: React.Dispatch<React.SetStateAction<Record[]>>
 }
 
 export function RecordItem({ record, setRecords }: Props) {
+  // 悬停在图片上时显示大图预览
+  const [showPreview, setShowPreview] = useState(false)
+  // 链接的标题和图标, 后台抓取完成前为空
+  const [linkPreview, setLinkPreview] = useState<LinkPreview | null>(null)
+
+  useEffect(() => {
+    if (record.subtype !== "url") return
+    invoke<LinkPreview | null>("get_link_preview", { url: record.value })
+      .then(setLinkPreview)
+      .catch(() => setLinkPreview(null))
+  }, [record.subtype, record.value])
+
+  // 代码高亮后的 HTML, 由后端生成并缓存
+  const [codePreview, setCodePreview] = useState<RecordPreview | null>(null)
+
+  useEffect(() => {
+    if (record.subtype !== "code") return
+    invoke<RecordPreview | null>("get_record_preview", { id: record.id })
+      .then(setCodePreview)
+      .catch(() => setCodePreview(null))
+  }, [record.id, record.subtype, record.value])
+
   const getColorValue = (hex: string) => {
     // 如果以#开头，移除#
     let colorValue = hex.startsWith("#") ? hex.substring(1) : hex
@@ -58,7 +97,10 @@ export function RecordItem({ record, setRecords }: Props) {
     }
     switch (record.record_type) {
       case "text":
-        return "文本"
+        if (record.subtype === "code" && record.language) {
+          return `代码 · ${record.language}`
+        }
+        return (record.subtype && SUBTYPE_TITLES[record.subtype]) || "文本"
       case "image":
         return "图像"
       case "file":
@@ -148,29 +190,63 @@ export function RecordItem({ record, setRecords }: Props) {
         {!!record.is_deleted && (
           <div className={styles.error}>文件已不存在</div>
         )}
+        {showPreview && record.preview_key && (
+          <div className={styles.preview}>
+            <img
+              src={convertFileSrc(record.preview_key, "clippy")}
+              alt="preview"
+            />
+          </div>
+        )}
         {record.record_type === "image" ? (
-          <img
-            src={`data:image/png;base64,${record.thumbnail}`}
-            alt="thumbnail"
-          />
+          record.thumbnail_key ? (
+            <img
+              src={convertFileSrc(record.thumbnail_key, "clippy")}
+              alt="thumbnail"
+              onMouseEnter={() => setShowPreview(true)}
+              onMouseLeave={() => setShowPreview(false)}
+            />
+          ) : (
+            <div className={styles.text}>图片过大或格式不支持, 无法预览</div>
+          )
         ) : (
-          <div
-            className={styles.text}
-            style={
-              isColor
-                ? {
-                    color: getInvertColor(record.value),
-                    fontSize: 24,
-                    display: "flex",
-                    alignItems: "center",
-                    justifyContent: "center",
-                    height: "100%",
-                  }
-                : undefined
-            }
-          >
-            {record.display_text || record.value}
-          </div>
+          <>
+            {linkPreview && (
+              <div className={styles.link} title={linkPreview.description}>
+                {linkPreview.favicon_key && (
+                  <img
+                    src={convertFileSrc(linkPreview.favicon_key, "clippy")}
+                    alt="favicon"
+                  />
+                )}
+                <span>{linkPreview.title}</span>
+              </div>
+            )}
+            {codePreview ? (
+              <div
+                className={styles.code}
+                dangerouslySetInnerHTML={{ __html: codePreview.html }}
+              />
+            ) : (
+              <div
+                className={styles.text}
+                style={
+                  isColor
+                    ? {
+                        color: getInvertColor(record.value),
+                        fontSize: 24,
+                        display: "flex",
+                        alignItems: "center",
+                        justifyContent: "center",
+                        height: "100%",
+                      }
+                    : undefined
+                }
+              >
+                {record.display_text || record.value}
+              </div>
+            )}
+          </>
         )}
       </div>
       {!isColor && (
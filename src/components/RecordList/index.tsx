@@ -9,7 +9,7 @@ import { invoke } from "@tauri-apps/api/core"
 import classNames from "classnames"
 import { debounce } from "lodash-es"
 
-import { type Record } from "../../utils/db"
+import { type CommandError, type Record } from "../../utils/db"
 import { RecordItem } from "../RecordItem"
 import { EmptyState } from "../EmptyState"
 import styles from "./styles.module.scss"
@@ -51,12 +51,13 @@ export const RecordList = forwardRef<RecordListRef, Props>(function RecordList(
     }, 150) // 150ms 的延迟，在响应速度和双击检测之间取得平衡
   }
 
-  const handleDoubleClick = (record: Record) => {
+  // 按住 Shift 时将图片作为文件粘贴
+  const handleDoubleClick = (record: Record, asFile: boolean) => {
     if (clickTimer.current) {
       clearTimeout(clickTimer.current)
       clickTimer.current = null
     }
-    choose(record)
+    choose(record, asFile)
   }
 
   // 滚动选中的项目到合适位置
@@ -100,7 +101,7 @@ export const RecordList = forwardRef<RecordListRef, Props>(function RecordList(
         }
         case "Enter": {
           if (currentIndex !== -1) {
-            choose(records[currentIndex])
+            choose(records[currentIndex], e.shiftKey)
           }
           break
         }
@@ -130,9 +131,10 @@ export const RecordList = forwardRef<RecordListRef, Props>(function RecordList(
     },
   }))
 
-  async function choose(record: Record) {
-    await invoke("choose", { record }).catch((e) => {
-      if (e === "File not found") {
+  async function choose(record: Record, asFile = false) {
+    const input = { ...record, as_file: asFile }
+    await invoke("choose", { record: input }).catch((e) => {
+      if ((e as CommandError)?.code === "file_not_found") {
         setRecords((rs) => {
           const index = rs.findIndex((r) => r.id === record.id)
           if (index === -1) return rs
@@ -185,7 +187,7 @@ export const RecordList = forwardRef<RecordListRef, Props>(function RecordList(
               [styles.deleted]: record.is_deleted,
             })}
             onClick={() => handleClick(record, index)}
-            onDoubleClick={() => handleDoubleClick(record)}
+            onDoubleClick={(e) => handleDoubleClick(record, e.shiftKey)}
           >
             <RecordItem record={record} setRecords={setRecords} />
           </div>
@@ -12,30 +12,155 @@ export interface Record {
   value: string
   /** 前端自定义属性, 解决文本过长时卡顿 */
   display_text?: string
-  thumbnail?: string
+  /** 缩略图和预览图在 blob store 中的 key, 通过 clippy:// 协议访问 */
+  thumbnail_key?: string
+  preview_key?: string
   size?: number
   img_size?: string
+  /** 文本的子类型: plain, url, email, phone, color, json, code, path, number, date */
+  subtype?: string
+  /** 代码语言, 仅 subtype 为 code 时有值 */
+  language?: string
+  /** 复制时激活的应用 bundle id */
+  source_app?: string
+  /** 复制次数, 重复复制时累加 */
+  copy_count: number
+  /** 从面板选择粘贴的次数 */
+  paste_count: number
+  last_used_at?: string
+  /** 过期时间(UTC), 到期后自动删除 */
+  expires_at?: string
   created_at: string
   updated_at: string
   is_deleted?: boolean
   favorite: boolean
 }
 
+// 后端命令失败时返回的错误
+export interface CommandError {
+  code: string
+  message: string
+}
+
+export interface LinkPreview {
+  url: string
+  title?: string
+  description?: string
+  favicon_key?: string
+}
+
+export interface RecordPreview {
+  language?: string
+  html: string
+}
+
 interface QueryParams {
-  last_updated_at?: string
+  // 上一页返回的 next_cursor
+  cursor?: string
   limit: number
   keyword?: string
   record_type?: RecordType
   favorite?: boolean
+  subtype?: string
+  language?: string
+  sort?: "recent" | "frequency" | "size" | "frecency"
+  record_types?: RecordType[]
+  // 时间范围(UTC), 格式为 YYYY-MM-DD 或 YYYY-MM-DD HH:MM:SS
+  created_after?: string
+  created_before?: string
+  updated_after?: string
+  updated_before?: string
+  min_size?: number
+  max_size?: number
+  min_width?: number
+  max_width?: number
+  min_height?: number
+  max_height?: number
+  extensions?: string[]
+  source_apps?: string[]
+}
+
+export interface RecordPage {
+  records: Record[]
+  // 为空表示没有更早的记录
+  next_cursor?: string
+  prev_cursor?: string
 }
 
-export async function getRecords(params: QueryParams): Promise<Record[]> {
-  let data: Record[] = await invoke("get_records", { params })
-  data = data.map((r) => {
+export async function getRecords(params: QueryParams): Promise<RecordPage> {
+  const page: RecordPage = await invoke("get_records", { params })
+  const records = page.records.map((r) => {
     if (r.record_type === "text" && r.value.length > 250) {
       return { ...r, display_text: r.value.slice(0, 250) }
     }
     return r
   })
-  return data
+  return { ...page, records }
+}
+
+// 剪贴板使用习惯的统计, days 为 0 时统计全部历史
+export interface StatisticsParams {
+  days?: number
+  bucket?: "day" | "week" | "month"
+  limit?: number
+}
+
+export interface Statistics {
+  record_count: number
+  copy_count: number
+  paste_count: number
+  items_per_period: { period: string; count: number }[]
+  top_source_apps: { source_app: string; count: number }[]
+  record_types: { record_type: RecordType; count: number; size_bytes: number }[]
+  image_storage: { count: number; original_bytes: number; thumbnail_bytes: number }
+  most_reused: {
+    id: number
+    record_type: RecordType
+    preview: string
+    thumbnail_key?: string
+    copy_count: number
+    paste_count: number
+    last_used_at?: string
+  }[]
+}
+
+export async function getStatistics(params: StatisticsParams = {}): Promise<Statistics> {
+  return invoke("get_statistics", { params })
+}
+
+export interface MaintenanceReport {
+  integrity_ok: boolean
+  integrity_error?: string
+  size_before: number
+  size_after: number
+  reclaimed_bytes: number
+  backup_count: number
+}
+
+export async function runMaintenance(): Promise<MaintenanceReport> {
+  return invoke("run_maintenance")
+}
+
+// ttlSecs 为空时取消过期
+export async function setRecordExpiry(id: number, ttlSecs?: number): Promise<void> {
+  return invoke("set_record_expiry", { id, ttlSecs })
+}
+
+export interface CaptureStatus {
+  paused: boolean
+  // 自动恢复的时间(unix 毫秒), 一直暂停时为空
+  resume_at?: number
+}
+
+// minutes 为空时一直暂停到手动恢复
+export async function pauseCapture(minutes?: number): Promise<CaptureStatus> {
+  return invoke("pause_capture", { minutes })
+}
+
+export async function resumeCapture(): Promise<CaptureStatus> {
+  return invoke("resume_capture")
+}
+
+export async function getCaptureStatus(): Promise<CaptureStatus> {
+  return invoke("get_capture_status")
 }